@@ -0,0 +1,204 @@
+use crate::error::Error;
+use crate::journal::{self, Journal};
+use crate::status::Status;
+use crate::task::Task;
+use crate::{deserialize_tasks, serialize_tasks, TaskList};
+use chrono::{DateTime, Duration, Utc};
+use std::path::{Path, PathBuf};
+
+/// Tasks closed longer ago than this are archived when no age is given.
+pub const DEFAULT_DAYS: u32 = 30;
+
+/// The archive kept beside `tasks_path`, with the same storage backend,
+/// e.g. `todo.archive.json` for `todo.json`.
+pub fn path_for(tasks_path: &Path) -> PathBuf {
+    let stem = tasks_path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let extension = tasks_path
+        .extension()
+        .map_or_else(|| "json".into(), |e| e.to_string_lossy());
+    tasks_path.with_file_name(format!("{}.archive.{}", stem, extension))
+}
+
+/// Reads the archive at `path`. A missing archive is empty, and reading
+/// one never creates it.
+pub fn load(path: &Path) -> Result<TaskList, Error> {
+    if path.exists() {
+        deserialize_tasks(path)
+    } else {
+        Ok(TaskList::default())
+    }
+}
+
+/// When a completed or cancelled task was closed. A task restored from the
+/// archive since counts as closed when it was restored, so that it is not
+/// archived again straight away.
+fn closed_at(task: &Task) -> Option<DateTime<Utc>> {
+    match task.status {
+        Status::Completed | Status::Cancelled => {
+            let closed = task
+                .history
+                .last()
+                .map(|change| change.at)
+                .or(task.completed)
+                .unwrap_or(task.created);
+            Some(
+                task.restored
+                    .map_or(closed, |restored| restored.max(closed)),
+            )
+        }
+        _ => None,
+    }
+}
+
+/// The tasks closed more than `days` days before `now`. Tasks with a
+/// subtask that stays behind are kept, so no task is left with a parent
+/// that is not in its list. Nothing was closed before the earliest time
+/// there is.
+pub fn closed_before(tasks: &TaskList, days: u32, now: DateTime<Utc>) -> Vec<usize> {
+    let cutoff = match Duration::try_days(days as i64).and_then(|age| now.checked_sub_signed(age)) {
+        Some(cutoff) => cutoff,
+        None => return Vec::new(),
+    };
+    let mut ids: Vec<usize> = tasks
+        .tasks
+        .values()
+        .filter(|task| closed_at(task).is_some_and(|closed| closed < cutoff))
+        .map(|task| task.id)
+        .collect();
+    loop {
+        let kept = |id: &usize| !ids.contains(id);
+        let staying: Vec<usize> = tasks
+            .tasks
+            .values()
+            .filter(|task| kept(&task.id))
+            .filter_map(|task| task.parent)
+            .collect();
+        let before = ids.len();
+        ids.retain(|id| !staying.contains(id));
+        if ids.len() == before {
+            return ids;
+        }
+    }
+}
+
+/// Moves tasks `ids` from `tasks` into the archive at `path`, keeping their
+/// IDs. Tasks left behind stop depending on them, as they are closed. The
+/// archive is saved and journaled first, like the target of a move.
+pub fn archive(
+    ids: &[usize],
+    tasks: &mut TaskList,
+    path: &Path,
+    command: &str,
+) -> Result<(), Error> {
+    let mut archived = load(path)?;
+    let before = archived.clone();
+    for id in ids {
+        let task = tasks.tasks.remove(id).ok_or(Error::NotFound(*id))?;
+        archived.tasks.insert(task.id, task);
+    }
+    for task in tasks.tasks.values_mut() {
+        task.depends_on.retain(|id| !ids.contains(id));
+    }
+    archived.next_id = archived.next_id.max(tasks.next_id);
+    save(path, &before, &archived, command)
+}
+
+/// Copies tasks `ids` from the archive at `path` back into `tasks` with
+/// their old IDs, noting when they were restored. Links to tasks no longer
+/// in the list are dropped. The
+/// archive keeps its copies, hidden while the tasks are back in the list,
+/// so that undoing the restore cannot lose them; archiving them again
+/// replaces them.
+pub fn restore(ids: &[usize], tasks: &mut TaskList, path: &Path) -> Result<(), Error> {
+    let archived = load(path)?;
+    let now = Utc::now();
+    for id in ids {
+        if tasks.tasks.contains_key(id) {
+            return Err(Error::Validation(format!("Task {} is not archived", id)));
+        }
+        let mut task = archived.tasks.get(id).ok_or(Error::NotFound(*id))?.clone();
+        let present = |other: &usize| tasks.tasks.contains_key(other) || ids.contains(other);
+        if !task.parent.is_some_and(|p| present(&p)) {
+            task.parent = None;
+        }
+        task.depends_on.retain(|d| present(d));
+        task.restored = Some(now);
+        tasks.tasks.insert(task.id, task);
+        println!("Restored task {}", id);
+    }
+    tasks.next_id = tasks.next_id.max(archived.next_id);
+    Ok(())
+}
+
+/// The archived tasks not also in `tasks`, which holds any that were
+/// restored or whose archiving was undone.
+pub fn only_archived<'a>(
+    archived: &'a TaskList,
+    tasks: &'a TaskList,
+) -> impl Iterator<Item = &'a Task> {
+    archived
+        .tasks
+        .values()
+        .filter(|task| !tasks.tasks.contains_key(&task.id))
+}
+
+fn save(path: &Path, before: &TaskList, after: &TaskList, command: &str) -> Result<(), Error> {
+    let journal_path = journal::path_for(path);
+    let mut journal = Journal::load(&journal_path)?;
+    journal.record(command.to_string(), before, after);
+    serialize_tasks(path, before, after)?;
+    journal.save(&journal_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn closed(id: usize, at: DateTime<Utc>, restored: Option<DateTime<Utc>>) -> Task {
+        let mut task = Task::new(format!("task {}", id));
+        task.id = id;
+        task.created = at;
+        task.status = Status::Completed;
+        task.completed = Some(at);
+        task.restored = restored;
+        task
+    }
+
+    fn list(tasks: Vec<Task>) -> TaskList {
+        TaskList {
+            next_id: tasks.len() + 1,
+            tasks: tasks.into_iter().map(|task| (task.id, task)).collect(),
+        }
+    }
+
+    #[test]
+    fn counts_restored_tasks_from_their_restore() {
+        let now = Utc::now();
+        let long_ago = now - Duration::days(40);
+        let tasks = list(vec![
+            closed(1, long_ago, None),
+            closed(2, long_ago, Some(now - Duration::days(2))),
+            closed(3, long_ago, Some(now - Duration::days(35))),
+        ]);
+        assert_eq!(closed_before(&tasks, 30, now), vec![1, 3]);
+        assert_eq!(closed_before(&tasks, 1, now), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn keeps_parents_of_tasks_that_stay() {
+        let now = Utc::now();
+        let mut child = closed(2, now, None);
+        child.parent = Some(1);
+        let tasks = list(vec![closed(1, now - Duration::days(40), None), child]);
+        assert!(closed_before(&tasks, 30, now).is_empty());
+    }
+
+    #[test]
+    fn archives_nothing_for_ages_beyond_the_calendar() {
+        let tasks = list(vec![closed(1, DateTime::<Utc>::MIN_UTC, None)]);
+        assert!(closed_before(&tasks, u32::MAX, Utc::now()).is_empty());
+    }
+}
@@ -0,0 +1,159 @@
+use fs2::FileExt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Replaces the file at `path` with `contents` so that readers, and the file
+/// left behind by a crash, see either the old contents or the new ones and
+/// never a truncated mix. The data goes to a temporary file in the same
+/// directory, is flushed to disk, and is then renamed over `path`.
+///
+/// If `path` is a symlink, the file it points to is replaced instead, so the
+/// link keeps working, and the replacement keeps the old file's permissions.
+pub fn write(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let path = &resolve(path);
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?;
+    let temp = path.with_file_name(format!(
+        ".{}.{}.tmp",
+        name.to_string_lossy(),
+        std::process::id()
+    ));
+    let permissions = fs::metadata(path).ok().map(|m| m.permissions());
+    let result = write_synced(&temp, contents)
+        .and_then(|_| match permissions {
+            Some(permissions) => fs::set_permissions(&temp, permissions),
+            None => Ok(()),
+        })
+        .and_then(|_| fs::rename(&temp, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result?;
+    sync_dir(path)
+}
+
+/// The file a write to `path` should replace: the target of any symlinks,
+/// including one that points to a file not created yet.
+fn resolve(path: &Path) -> PathBuf {
+    let mut path = path.to_path_buf();
+    // Bounded, so a symlink loop ends in an error from the write instead.
+    for _ in 0..40 {
+        if let Ok(real) = fs::canonicalize(&path) {
+            return real;
+        }
+        match fs::read_link(&path) {
+            Ok(target) => {
+                let dir = path.parent().unwrap_or_else(|| Path::new(""));
+                path = dir.join(target);
+            }
+            Err(_) => break,
+        }
+    }
+    path
+}
+
+fn write_synced(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    file.write_all(contents)?;
+    file.sync_all()
+}
+
+/// Flushes the directory entry created by the rename. Only Unix supports
+/// opening a directory for this; elsewhere the rename is left to the OS.
+#[cfg(unix)]
+fn sync_dir(path: &Path) -> io::Result<()> {
+    match path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        Some(dir) => File::open(dir)?.sync_all(),
+        None => File::open(".")?.sync_all(),
+    }
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/// An advisory lock on a task file, released when dropped. The lock is held
+/// on a separate `<name>.lock` file, since the task file itself is replaced
+/// on every write.
+pub struct Lock {
+    file: File,
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        let _ = self.file.unlock();
+    }
+}
+
+pub fn lock_path(path: &Path) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    path.with_file_name(format!("{}.lock", stem))
+}
+
+/// Locks the task file at `path`, waiting for any other `todo` process to
+/// finish with it first. Readers share the lock; writers hold it alone.
+pub fn lock(path: &Path, exclusive: bool) -> io::Result<Lock> {
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(lock_path(path))?;
+    let attempt = if exclusive {
+        file.try_lock_exclusive()
+    } else {
+        FileExt::try_lock_shared(&file)
+    };
+    if attempt.is_err() {
+        eprintln!(
+            "Waiting for another todo process to release {}",
+            path.display()
+        );
+        if exclusive {
+            file.lock_exclusive()?;
+        } else {
+            FileExt::lock_shared(&file)?;
+        }
+    }
+    Ok(Lock { file })
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::{symlink, PermissionsExt};
+
+    #[test]
+    fn writes_through_symlinks_keeping_permissions() {
+        let dir = std::env::temp_dir().join(format!("todo-atomic-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("sync")).unwrap();
+        let real = dir.join("sync").join("real.json");
+        let link = dir.join("todo.json");
+        symlink("sync/real.json", &link).unwrap();
+
+        write(&link, b"first").unwrap();
+        assert!(fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(fs::read_to_string(&real).unwrap(), "first");
+
+        fs::set_permissions(&real, fs::Permissions::from_mode(0o600)).unwrap();
+        write(&link, b"second").unwrap();
+        assert!(fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(fs::read_to_string(&real).unwrap(), "second");
+        let mode = fs::metadata(&real).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
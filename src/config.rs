@@ -0,0 +1,406 @@
+use crate::atomic;
+use crate::color::{Color, ColorMode};
+use crate::error::{Error, ParseError};
+use crate::filter::Filter;
+use crate::status::Status;
+use crate::Sort;
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Local, Utc};
+use serde::{Deserialize, Deserializer};
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use toml::value::{Table, Value};
+
+/// Settings read from `config.toml` in the user's config directory, e.g.
+/// `~/.config/todo/config.toml`:
+///
+/// ```toml
+/// file = "~/Dropbox/todo.json"
+/// filter = "status:open"
+/// sort = "due"
+/// date-format = "%a %d %b"
+/// color = "auto"
+/// archive-after = 30
+///
+/// [theme]
+/// overdue = "red"
+///
+/// [glyphs]
+/// completed = "x"
+/// ```
+///
+/// Command line flags take precedence over every setting.
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Config {
+    /// Task file used when neither `--json` nor `TODO_FILE` is given.
+    pub file: Option<PathBuf>,
+    /// Filter for `ls` when no `--filter` is given.
+    #[serde(default, deserialize_with = "parsed")]
+    pub filter: Option<Filter>,
+    /// Order for `ls` when no `--sort` is given.
+    #[serde(default, deserialize_with = "parsed")]
+    pub sort: Option<Sort>,
+    /// Shows due dates in this strftime format rather than relative to now.
+    #[serde(default, deserialize_with = "parsed")]
+    pub date_format: Option<DateFormat>,
+    #[serde(default, deserialize_with = "parsed")]
+    pub color: Option<ColorMode>,
+    /// Archives tasks closed more than this many days ago whenever a command
+    /// that changes tasks is run, rather than only when `archive` is run.
+    #[serde(default, deserialize_with = "days")]
+    pub archive_after: Option<u32>,
+    #[serde(default)]
+    pub theme: Theme,
+    #[serde(default)]
+    pub glyphs: Glyphs,
+}
+
+/// Colors used by `ls`, `show` and `search` when color is enabled.
+#[derive(Deserialize, Debug)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Theme {
+    pub overdue: Color,
+    pub due: Color,
+    pub priority: Color,
+    pub tag: Color,
+    pub completed: Color,
+    pub cancelled: Color,
+    /// Words matching a `search`.
+    pub highlight: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            overdue: Color::Red,
+            due: Color::Yellow,
+            priority: Color::Bold,
+            tag: Color::Cyan,
+            completed: Color::Green,
+            cancelled: Color::Gray,
+            highlight: Color::Magenta,
+        }
+    }
+}
+
+/// The character shown for each status by `ls`.
+#[derive(Deserialize, Debug)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Glyphs {
+    pub pending: char,
+    pub in_progress: char,
+    pub blocked: char,
+    pub waiting: char,
+    pub completed: char,
+    pub cancelled: char,
+    pub recurring: char,
+}
+
+impl Default for Glyphs {
+    fn default() -> Self {
+        Glyphs {
+            pending: Status::Pending.glyph(),
+            in_progress: Status::InProgress.glyph(),
+            blocked: Status::Blocked.glyph(),
+            waiting: Status::Waiting.glyph(),
+            completed: Status::Completed.glyph(),
+            cancelled: Status::Cancelled.glyph(),
+            recurring: Status::Recurring.glyph(),
+        }
+    }
+}
+
+impl Glyphs {
+    pub fn get(&self, status: Status) -> char {
+        match status {
+            Status::Pending => self.pending,
+            Status::InProgress => self.in_progress,
+            Status::Blocked => self.blocked,
+            Status::Waiting => self.waiting,
+            Status::Completed => self.completed,
+            Status::Cancelled => self.cancelled,
+            Status::Recurring => self.recurring,
+        }
+    }
+}
+
+/// A strftime format, checked when the config is read so that formatting
+/// a date can never fail.
+#[derive(Debug)]
+pub struct DateFormat(String);
+
+impl DateFormat {
+    pub fn format(&self, time: DateTime<Utc>) -> String {
+        time.with_timezone(&Local).format(&self.0).to_string()
+    }
+}
+
+impl FromStr for DateFormat {
+    type Err = ParseError;
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        if StrftimeItems::new(format).any(|item| item == Item::Error) {
+            return Err(ParseError::new("date format", format));
+        }
+        Ok(DateFormat(format.to_string()))
+    }
+}
+
+/// Deserializes an optional string setting through its `FromStr` impl.
+fn parsed<'de, D, T>(d: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    match Option::<String>::deserialize(d)? {
+        Some(value) => value.parse().map(Some).map_err(serde::de::Error::custom),
+        None => Ok(None),
+    }
+}
+
+/// A number of days as written in the config file.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Days {
+    Number(i64),
+    Text(String),
+}
+
+/// Deserializes a number of days, also accepting it quoted, as older builds
+/// wrote it that way with `config set`.
+fn days<'de, D: Deserializer<'de>>(d: D) -> Result<Option<u32>, D::Error> {
+    let (days, written) = match Option::<Days>::deserialize(d)? {
+        None => return Ok(None),
+        Some(Days::Number(days)) => (u32::try_from(days).ok(), days.to_string()),
+        Some(Days::Text(days)) => (days.trim().parse().ok(), format!("\"{}\"", days)),
+    };
+    days.map(Some).ok_or_else(|| {
+        serde::de::Error::custom(format!(
+            "expected a whole number of days, found {}",
+            written
+        ))
+    })
+}
+
+pub fn path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("todo").join("config.toml"))
+}
+
+impl Config {
+    /// Reads the config file, treating a missing file as an empty config.
+    pub fn load() -> Result<Config, Error> {
+        let path = match path() {
+            Some(path) => path,
+            None => return Ok(Config::default()),
+        };
+        match fs::read_to_string(&path) {
+            Ok(contents) => toml::from_str(&contents).map_err(|e| Error::toml(&path, e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(e) => Err(Error::io("read", &path, e)),
+        }
+    }
+}
+
+/// Reads a setting such as `sort` or `theme.overdue` as written in the
+/// config file.
+pub fn get(key: &str) -> Result<Option<String>, Error> {
+    let mut value = Value::Table(read_table(&required_path()?)?);
+    for part in key.split('.') {
+        value = match value {
+            Value::Table(mut table) => match table.remove(part) {
+                Some(value) => value,
+                None => return Ok(None),
+            },
+            _ => return Ok(None),
+        };
+    }
+    Ok(Some(match value {
+        Value::String(s) => s,
+        other => other.to_string(),
+    }))
+}
+
+/// Sets a setting, or removes it when `value` is `None`, rewriting the
+/// config file. A value is written as a number if the setting takes one,
+/// and as a string otherwise. The result is checked before it is written,
+/// so the file is never left with a setting that cannot be read back.
+/// Comments in the file are not preserved.
+pub fn set(key: &str, value: Option<&str>) -> Result<(), Error> {
+    let path = required_path()?;
+    let root = read_table(&path)?;
+    let candidates: Vec<Option<Value>> = match value {
+        Some(value) => {
+            let number = value.parse().ok().map(Value::Integer);
+            let text = Value::String(value.to_string());
+            number.into_iter().chain(Some(text)).map(Some).collect()
+        }
+        None => vec![None],
+    };
+    let mut updated = Err(None);
+    for candidate in candidates {
+        let table = with_setting(&root, key, candidate)?;
+        match Value::Table(table.clone()).try_into::<Config>() {
+            Ok(_) => {
+                updated = Ok(table);
+                break;
+            }
+            Err(e) => updated = Err(Some(e)),
+        }
+    }
+    let root = updated.map_err(|e| {
+        let reason = e.map_or_else(String::new, |e| format!(": {}", e));
+        Error::Validation(format!("Could not set `{}`{}", key, reason))
+    })?;
+    let contents = toml::to_string(&Value::Table(root)).expect("a table serializes to TOML");
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| Error::io("create", dir, e))?;
+    }
+    atomic::write(&path, contents.as_bytes()).map_err(|e| Error::io("write", &path, e))
+}
+
+/// A copy of `root` with the setting `key` set to `value`, or removed when
+/// `value` is `None`.
+fn with_setting(root: &Table, key: &str, value: Option<Value>) -> Result<Table, Error> {
+    let mut root = root.clone();
+    let parts: Vec<&str> = key.split('.').collect();
+    let (name, parents) = parts.split_last().expect("split yields at least one part");
+    let mut table = &mut root;
+    for parent in parents {
+        let entry = table
+            .entry(parent.to_string())
+            .or_insert_with(|| Value::Table(Table::new()));
+        table = match entry {
+            Value::Table(inner) => inner,
+            _ => return Err(Error::Validation(format!("`{}` is not a table", parent))),
+        };
+    }
+    match value {
+        Some(value) => {
+            table.insert(name.to_string(), value);
+        }
+        None => {
+            table.remove(*name);
+        }
+    }
+    Ok(root)
+}
+
+fn required_path() -> Result<PathBuf, Error> {
+    path().ok_or_else(|| Error::Validation("Could not find a config directory".to_string()))
+}
+
+fn read_table(path: &Path) -> Result<Table, Error> {
+    match fs::read_to_string(path) {
+        Ok(contents) => toml::from_str(&contents).map_err(|e| Error::toml(path, e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Table::new()),
+        Err(e) => Err(Error::io("read", path, e)),
+    }
+}
+
+/// Where the task file path came from, as reported by `todo where`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    Flag,
+    Env,
+    Config,
+    Default,
+    Legacy,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let source = match self {
+            Source::Flag => "--json".to_string(),
+            Source::Env => "TODO_FILE".to_string(),
+            Source::Config => match path() {
+                Some(path) => format!("file in {}", path.display()),
+                None => "config file".to_string(),
+            },
+            Source::Default => "default data directory".to_string(),
+            Source::Legacy => "legacy location; move it to the data directory".to_string(),
+        };
+        write!(f, "{}", source)
+    }
+}
+
+/// Picks the task file: `--json`, then `$TODO_FILE`, then the config file,
+/// then `todo.json` in the data directory, e.g. `~/.local/share/todo`.
+/// Files left in the home directory by older versions are still used until
+/// one exists in the data directory.
+pub fn task_file(flag: Option<PathBuf>, config: &Config) -> Result<(PathBuf, Source), Error> {
+    if let Some(path) = flag {
+        return Ok((path, Source::Flag));
+    }
+    if let Some(path) = env::var_os("TODO_FILE").filter(|p| !p.is_empty()) {
+        return Ok((expand_home(Path::new(&path)), Source::Env));
+    }
+    if let Some(path) = &config.file {
+        return Ok((expand_home(path), Source::Config));
+    }
+    let default = dirs::data_dir()
+        .map(|dir| dir.join("todo").join("todo.json"))
+        .ok_or_else(|| {
+            Error::Validation(
+                "Could not find a data directory; set TODO_FILE or pass --json".to_string(),
+            )
+        })?;
+    if !default.exists() {
+        if let Some(legacy) = dirs::home_dir().map(|home| home.join("todo.json")) {
+            if legacy.exists() {
+                return Ok((legacy, Source::Legacy));
+            }
+        }
+    }
+    Ok((default, Source::Default))
+}
+
+/// Expands a leading `~` to the home directory.
+fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), dirs::home_dir()) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn archive_after(toml: &str) -> Result<Option<u32>, String> {
+        toml::from_str::<Config>(toml)
+            .map(|config| config.archive_after)
+            .map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn archive_after_takes_a_number_of_days() {
+        assert_eq!(archive_after(""), Ok(None));
+        assert_eq!(archive_after("archive-after = 30"), Ok(Some(30)));
+        assert_eq!(archive_after("archive-after = \"30\""), Ok(Some(30)));
+        assert_eq!(archive_after("archive-after = 0"), Ok(Some(0)));
+        for bad in ["-1", "\"soon\"", "1.5"] {
+            let error = archive_after(&format!("archive-after = {}", bad));
+            assert!(error.is_err(), "{}", bad);
+        }
+        assert!(archive_after("archive-after = -1")
+            .unwrap_err()
+            .contains("expected a whole number of days, found -1"));
+    }
+
+    #[test]
+    fn settings_keep_their_type() {
+        let root = with_setting(&Table::new(), "archive-after", Some(Value::Integer(30))).unwrap();
+        let root = with_setting(&root, "theme.overdue", Some(Value::String("red".into()))).unwrap();
+        assert_eq!(
+            toml::to_string(&Value::Table(root.clone())).unwrap(),
+            "archive-after = 30\n\n[theme]\noverdue = \"red\"\n"
+        );
+        let root = with_setting(&root, "archive-after", None).unwrap();
+        assert!(!root.contains_key("archive-after"));
+        assert!(with_setting(&root, "theme.overdue.x", None).is_err());
+    }
+}
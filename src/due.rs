@@ -0,0 +1,289 @@
+use crate::error::ParseError;
+use chrono::{
+    DateTime, Datelike, Duration, Local, Months, NaiveDate, NaiveDateTime, TimeZone, Utc, Weekday,
+};
+
+/// Parses a due date given on the command line.
+///
+/// Accepts absolute dates (`2026-11-03`, `2026-11-03 14:00`, RFC 3339) and
+/// relative phrases (`now`, `today`, `eod`, `tomorrow`, `friday`,
+/// `next friday`, `in 3 days`, `eow`, `eom`). Anything that names a whole day
+/// resolves to the end of that day in local time, so a task due today is not
+/// overdue until midnight.
+pub fn parse(input: &str) -> Result<DateTime<Utc>, ParseError> {
+    parse_from(input, Local::now())
+}
+
+pub fn parse_from(input: &str, now: DateTime<Local>) -> Result<DateTime<Utc>, ParseError> {
+    resolve(&input.trim().to_lowercase(), now).ok_or_else(|| ParseError::new("due date", input))
+}
+
+/// Parses a day bounding a span of past time, such as a report. It takes the
+/// same forms as `parse`, except that a weekday names the most recent one,
+/// today included, and `last friday` the one before today.
+pub fn parse_past(input: &str) -> Result<DateTime<Utc>, ParseError> {
+    parse_past_from(input, Local::now())
+}
+
+pub fn parse_past_from(input: &str, now: DateTime<Local>) -> Result<DateTime<Utc>, ParseError> {
+    let lower = input.trim().to_lowercase();
+    let words: Vec<&str> = lower.split_whitespace().collect();
+    let today = now.date_naive();
+    let day = match words.as_slice() {
+        [day] => weekday(day).map(|day| previous_weekday(today, day, true)),
+        ["last", day] => weekday(day).map(|day| previous_weekday(today, day, false)),
+        _ => None,
+    };
+    match day {
+        Some(day) => end_of_day(day)
+            .map(|time| time.with_timezone(&Utc))
+            .ok_or_else(|| ParseError::new("date", input)),
+        None => parse_from(input, now),
+    }
+}
+
+fn resolve(input: &str, now: DateTime<Local>) -> Option<DateTime<Utc>> {
+    let words: Vec<&str> = input.split_whitespace().collect();
+    let today = now.date_naive();
+    let local = match words.as_slice() {
+        ["now"] => now,
+        ["today"] | ["eod"] => end_of_day(today)?,
+        ["tomorrow"] => end_of_day(today + Duration::days(1))?,
+        ["yesterday"] => end_of_day(today - Duration::days(1))?,
+        ["eow"] => {
+            end_of_day(today + Duration::days(6 - today.weekday().num_days_from_monday() as i64))?
+        }
+        ["eom"] => end_of_day(today.with_day(1)? + Months::new(1) - Duration::days(1))?,
+        ["next", day] => end_of_day(next_weekday(today, weekday(day)?, false))?,
+        [day] if weekday(day).is_some() => end_of_day(next_weekday(today, weekday(day)?, true))?,
+        ["in", amount, unit] => offset(now, amount.parse().ok()?, unit)?,
+        _ => return absolute(input),
+    };
+    Some(local.with_timezone(&Utc))
+}
+
+fn absolute(input: &str) -> Option<DateTime<Utc>> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(input) {
+        return Some(time.with_timezone(&Utc));
+    }
+    let local = match NaiveDateTime::parse_from_str(input, "%Y-%m-%d %H:%M") {
+        Ok(time) => local_time(time)?,
+        Err(_) => end_of_day(NaiveDate::parse_from_str(input, "%Y-%m-%d").ok()?)?,
+    };
+    Some(local.with_timezone(&Utc))
+}
+
+/// Adds `amount` units to `now`, or `None` if that is past the last date
+/// chrono can represent.
+fn offset(now: DateTime<Local>, amount: u32, unit: &str) -> Option<DateTime<Local>> {
+    let amount_i = amount as i64;
+    let today = now.date_naive();
+    match unit {
+        "minute" | "minutes" | "min" | "mins" => {
+            now.checked_add_signed(Duration::try_minutes(amount_i)?)
+        }
+        "hour" | "hours" | "h" => now.checked_add_signed(Duration::try_hours(amount_i)?),
+        "day" | "days" | "d" => {
+            end_of_day(today.checked_add_signed(Duration::try_days(amount_i)?)?)
+        }
+        "week" | "weeks" | "w" => {
+            end_of_day(today.checked_add_signed(Duration::try_weeks(amount_i)?)?)
+        }
+        "month" | "months" => end_of_day(today.checked_add_months(Months::new(amount))?),
+        _ => None,
+    }
+}
+
+pub fn weekday(word: &str) -> Option<Weekday> {
+    match word {
+        "mon" | "monday" => Some(Weekday::Mon),
+        "tue" | "tues" | "tuesday" => Some(Weekday::Tue),
+        "wed" | "wednesday" => Some(Weekday::Wed),
+        "thu" | "thurs" | "thursday" => Some(Weekday::Thu),
+        "fri" | "friday" => Some(Weekday::Fri),
+        "sat" | "saturday" => Some(Weekday::Sat),
+        "sun" | "sunday" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+/// The next date falling on `day`; today counts only if `include_today`.
+pub fn next_weekday(today: NaiveDate, day: Weekday, include_today: bool) -> NaiveDate {
+    let ahead = (day.num_days_from_monday() + 7 - today.weekday().num_days_from_monday()) % 7;
+    let ahead = if ahead == 0 && !include_today {
+        7
+    } else {
+        ahead
+    };
+    today + Duration::days(ahead as i64)
+}
+
+/// The last date falling on `day`; today counts only if `include_today`.
+pub fn previous_weekday(today: NaiveDate, day: Weekday, include_today: bool) -> NaiveDate {
+    let behind = (today.weekday().num_days_from_monday() + 7 - day.num_days_from_monday()) % 7;
+    let behind = if behind == 0 && !include_today {
+        7
+    } else {
+        behind
+    };
+    today - Duration::days(behind as i64)
+}
+
+pub fn end_of_day(date: NaiveDate) -> Option<DateTime<Local>> {
+    local_time(date.and_hms_opt(23, 59, 59)?)
+}
+
+pub fn local_time(time: NaiveDateTime) -> Option<DateTime<Local>> {
+    Local.from_local_datetime(&time).earliest()
+}
+
+/// Describes how far `due` is from `now`, e.g. "in 3 days" or "2 hours ago".
+/// Anything further off than a day is counted in local calendar days, so a
+/// task due tomorrow evening reads "in 1 day" whatever the time now.
+pub fn humanize(due: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let delta = due - now;
+    let seconds = delta.num_seconds().abs();
+    let days = (due.with_timezone(&Local).date_naive() - now.with_timezone(&Local).date_naive())
+        .num_days()
+        .abs();
+    let (amount, unit) = if seconds < 60 {
+        return "now".to_string();
+    } else if seconds < 3600 {
+        ((seconds + 30) / 60, "minute")
+    } else if seconds < 86400 || days == 0 {
+        ((seconds + 1800) / 3600, "hour")
+    } else if days < 14 {
+        (days, "day")
+    } else if days < 60 {
+        (days / 7, "week")
+    } else if days < 365 {
+        (days / 30, "month")
+    } else {
+        (days / 365, "year")
+    };
+    let plural = if amount == 1 { "" } else { "s" };
+    if delta.num_seconds() > 0 {
+        format!("in {} {}{}", amount, unit, plural)
+    } else {
+        format!("{} {}{} ago", amount, unit, plural)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Friday 2026-10-16, 10:30 local time.
+    fn now() -> DateTime<Local> {
+        local_time(
+            NaiveDate::from_ymd_opt(2026, 10, 16)
+                .and_then(|d| d.and_hms_opt(10, 30, 0))
+                .unwrap(),
+        )
+        .unwrap()
+    }
+
+    fn resolved(input: &str) -> String {
+        parse_from(input, now())
+            .unwrap_or_else(|e| panic!("{}: {}", input, e))
+            .with_timezone(&Local)
+            .format("%Y-%m-%d %H:%M")
+            .to_string()
+    }
+
+    #[test]
+    fn relative_forms() {
+        let cases = [
+            ("now", "2026-10-16 10:30"),
+            ("today", "2026-10-16 23:59"),
+            ("eod", "2026-10-16 23:59"),
+            ("tomorrow", "2026-10-17 23:59"),
+            ("yesterday", "2026-10-15 23:59"),
+            ("eow", "2026-10-18 23:59"),
+            ("eom", "2026-10-31 23:59"),
+            ("friday", "2026-10-16 23:59"),
+            ("fri", "2026-10-16 23:59"),
+            ("next friday", "2026-10-23 23:59"),
+            ("monday", "2026-10-19 23:59"),
+            ("next monday", "2026-10-19 23:59"),
+            ("Thursday", "2026-10-22 23:59"),
+            ("in 30 minutes", "2026-10-16 11:00"),
+            ("in 2 hours", "2026-10-16 12:30"),
+            ("in 3 days", "2026-10-19 23:59"),
+            ("in 1 week", "2026-10-23 23:59"),
+            ("in 2 months", "2026-12-16 23:59"),
+            ("  in 1 d ", "2026-10-17 23:59"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(resolved(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn past_forms() {
+        let cases = [
+            ("friday", "2026-10-16"),
+            ("last friday", "2026-10-09"),
+            ("monday", "2026-10-12"),
+            ("Sat", "2026-10-10"),
+            ("last thursday", "2026-10-15"),
+            ("today", "2026-10-16"),
+            ("yesterday", "2026-10-15"),
+            ("2026-10-01", "2026-10-01"),
+        ];
+        for (input, expected) in cases {
+            let day = parse_past_from(input, now())
+                .unwrap_or_else(|e| panic!("{}: {}", input, e))
+                .with_timezone(&Local)
+                .format("%Y-%m-%d")
+                .to_string();
+            assert_eq!(day, expected, "{}", input);
+        }
+        assert!(parse_past_from("last week", now()).is_err());
+    }
+
+    #[test]
+    fn end_of_month_clamps() {
+        let jan_31 = local_time(
+            NaiveDate::from_ymd_opt(2027, 1, 31)
+                .and_then(|d| d.and_hms_opt(9, 0, 0))
+                .unwrap(),
+        )
+        .unwrap();
+        let due = |input| {
+            parse_from(input, jan_31)
+                .unwrap()
+                .with_timezone(&Local)
+                .format("%Y-%m-%d")
+                .to_string()
+        };
+        assert_eq!(due("eom"), "2027-01-31");
+        assert_eq!(due("in 1 month"), "2027-02-28");
+    }
+
+    #[test]
+    fn absolute_forms() {
+        assert_eq!(resolved("2026-11-03"), "2026-11-03 23:59");
+        assert_eq!(resolved("2026-11-03 14:00"), "2026-11-03 14:00");
+        let rfc = parse_from("2026-11-03T14:00:00Z", now()).unwrap();
+        assert_eq!(rfc.to_rfc3339(), "2026-11-03T14:00:00+00:00");
+    }
+
+    #[test]
+    fn rejects_unknown_forms() {
+        for input in [
+            "",
+            "next week",
+            "in days",
+            "in -1 days",
+            "in 3 fortnights",
+            "in 4000000000 hours",
+            "in 4000000000 days",
+            "in 999999999 weeks",
+            "in 4000000000 months",
+            "2026-13-01",
+        ] {
+            assert!(parse_from(input, now()).is_err(), "{}", input);
+        }
+    }
+}
@@ -0,0 +1,255 @@
+use crate::error::{Error, ParseError};
+use crate::task::{self, Changes, Priority, Task};
+use crate::{due, project};
+use chrono::{DateTime, Local, Utc};
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const HEADER: &str = "\
+# Edit the fields below and save to apply the changes.
+# Lines starting with '#' are ignored. Leave a value empty to clear it.
+# Dates accept the same forms as --due, e.g. 2026-11-03 or next friday.
+";
+
+/// Opens `task` in the user's editor as a `field: value` document and
+/// returns whatever the user changed.
+pub fn edit_task(task: &Task) -> Result<Changes, Error> {
+    let original = render(task);
+    let edited = edit_text(&format!("todo-{}", task.id), &original)?;
+    changes(task, &original, &edited)
+}
+
+/// Opens the notes of `task` in the user's editor and returns them as
+/// saved, or `None` if they were emptied.
+pub fn edit_notes(task: &Task) -> Result<Option<String>, Error> {
+    let original = task.notes.clone().unwrap_or_default();
+    let edited = edit_text(&format!("todo-{}-notes", task.id), &original)?;
+    let notes = edited.trim_end();
+    Ok(Some(notes.to_string()).filter(|n| !n.trim().is_empty()))
+}
+
+/// Writes `text` to a temporary file named after `name`, lets the user edit
+/// it, and returns the result.
+fn edit_text(name: &str, text: &str) -> Result<String, Error> {
+    let (mut file, temp) = TempFile::create(name)?;
+    let path = &temp.path;
+    file.write_all(text.as_bytes())
+        .and_then(|_| file.sync_all())
+        .map_err(|e| Error::io("write", path, e))?;
+    drop(file);
+    open_editor(path)?;
+    fs::read_to_string(path).map_err(|e| Error::io("read", path, e))
+}
+
+/// A file in the shared temporary directory, removed when dropped so that
+/// task details never outlive the edit, however it ends.
+struct TempFile {
+    path: PathBuf,
+}
+
+impl TempFile {
+    /// Creates a new file only the user can read. The name is hard to guess
+    /// and must not exist yet, so a file or symlink planted by someone else
+    /// is never written through.
+    fn create(name: &str) -> Result<(File, TempFile), Error> {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.subsec_nanos());
+        let mut attempt = 0;
+        loop {
+            let path = env::temp_dir().join(format!(
+                "{}-{}-{:08x}.txt",
+                name,
+                std::process::id(),
+                nanos.wrapping_add(attempt)
+            ));
+            let mut options = OpenOptions::new();
+            options.write(true).create_new(true);
+            #[cfg(unix)]
+            options.mode(0o600);
+            match options.open(&path) {
+                Ok(file) => return Ok((file, TempFile { path })),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists && attempt < 100 => {
+                    attempt += 1;
+                }
+                Err(e) => return Err(Error::io("create", &path, e)),
+            }
+        }
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+fn open_editor(path: &Path) -> Result<(), Error> {
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    // Allow editors configured with arguments, such as "code --wait".
+    let mut words = editor.split_whitespace();
+    let program = words
+        .next()
+        .ok_or_else(|| Error::Validation("$EDITOR is empty".to_string()))?;
+    let status = Command::new(program)
+        .args(words)
+        .arg(path)
+        .status()
+        .map_err(|e| Error::io("run editor", Path::new(program), e))?;
+    if !status.success() {
+        return Err(Error::Validation(format!(
+            "Editor exited with {}; the task was not changed",
+            status
+        )));
+    }
+    Ok(())
+}
+
+fn render(task: &Task) -> String {
+    let due = task.due.map(format_due).unwrap_or_default();
+    let priority = task
+        .priority
+        .map(|p| format!("{:?}", p).to_lowercase())
+        .unwrap_or_default();
+    let tags = task
+        .tags
+        .iter()
+        .map(|tag| format!("+{}", tag))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let parent = task.parent.map(|p| p.to_string()).unwrap_or_default();
+    let depends_on = task
+        .depends_on
+        .iter()
+        .map(|id| id.to_string())
+        .collect::<Vec<_>>()
+        .join(" ");
+    format!(
+        "{}\ndescription: {}\ndue: {}\npriority: {}\nproject: {}\ntags: {}\nparent: {}\ndepends on: {}\n",
+        HEADER,
+        task.description,
+        due,
+        priority,
+        task.project.as_deref().unwrap_or(""),
+        tags,
+        parent,
+        depends_on
+    )
+}
+
+fn format_due(due: DateTime<Utc>) -> String {
+    due.with_timezone(&Local)
+        .format("%Y-%m-%d %H:%M")
+        .to_string()
+}
+
+struct Field {
+    line: usize,
+    key: String,
+    value: String,
+}
+
+fn parse_error(line: usize, message: String) -> Error {
+    Error::Parse {
+        location: "edited task".to_string(),
+        line,
+        column: None,
+        message,
+    }
+}
+
+fn fields(document: &str) -> Result<Vec<Field>, Error> {
+    let mut fields = Vec::new();
+    for (number, line) in document.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (key, value) = line
+            .split_once(':')
+            .ok_or_else(|| parse_error(number + 1, "expected `field: value`".to_string()))?;
+        fields.push(Field {
+            line: number + 1,
+            key: key.trim().to_lowercase(),
+            value: value.trim().to_string(),
+        });
+    }
+    Ok(fields)
+}
+
+fn task_id(value: &str) -> Result<usize, ParseError> {
+    value.parse().map_err(|_| ParseError::new("task ID", value))
+}
+
+/// Compares the edited document with the one originally rendered. Only
+/// fields whose text changed are parsed, so untouched values such as the
+/// seconds of a due date are never rounded.
+fn changes(task: &Task, original: &str, edited: &str) -> Result<Changes, Error> {
+    let before = fields(original)?;
+    let mut changes = Changes::default();
+    for Field { line, key, value } in fields(edited)? {
+        let unchanged = before.iter().any(|f| f.key == key && f.value == value);
+        let invalid = |e: ParseError| parse_error(line, e.to_string());
+        match key.as_str() {
+            _ if unchanged => {}
+            "description" if value.is_empty() => {
+                return Err(parse_error(line, "description cannot be empty".to_string()))
+            }
+            "description" => changes.description = Some(value),
+            "due" if value.is_empty() => changes.due = Some(None),
+            "due" => changes.due = Some(Some(due::parse(&value).map_err(invalid)?)),
+            "priority" if value.is_empty() => changes.priority = Some(None),
+            "priority" => {
+                changes.priority = Some(Some(value.parse::<Priority>().map_err(invalid)?))
+            }
+            "project" if value.is_empty() => changes.project = Some(None),
+            "project" => changes.project = Some(Some(project::parse(&value).map_err(invalid)?)),
+            "tags" => {
+                let tags = value
+                    .split_whitespace()
+                    .map(task::parse_tag)
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(invalid)?;
+                changes.remove_tags = task
+                    .tags
+                    .iter()
+                    .filter(|t| !tags.contains(t))
+                    .cloned()
+                    .collect();
+                changes.add_tags = tags
+                    .into_iter()
+                    .filter(|t| !task.tags.contains(t))
+                    .collect();
+            }
+            "parent" if value.is_empty() => changes.parent = Some(None),
+            "parent" => changes.parent = Some(Some(task_id(&value).map_err(invalid)?)),
+            "depends on" => {
+                let ids = value
+                    .split_whitespace()
+                    .map(task_id)
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(invalid)?;
+                changes.remove_dependencies = task
+                    .depends_on
+                    .iter()
+                    .filter(|id| !ids.contains(id))
+                    .copied()
+                    .collect();
+                changes.add_dependencies = ids
+                    .into_iter()
+                    .filter(|id| !task.depends_on.contains(id))
+                    .collect();
+            }
+            _ => return Err(parse_error(line, format!("unknown field `{}`", key))),
+        }
+    }
+    Ok(changes)
+}
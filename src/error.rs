@@ -0,0 +1,182 @@
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Everything that can make a command fail. Each variant maps to its own
+/// process exit code, so scripts can tell a missing task from a broken file:
+///
+/// | Code | Failure |
+/// |------|---------|
+/// | 3    | no task has the given ID |
+/// | 4    | the command does not make sense for the tasks as they are |
+/// | 64   | the command line could not be understood |
+/// | 65   | a task file, journal, config or edited document is malformed |
+/// | 74   | a file or database could not be read, written or locked |
+#[derive(Debug)]
+pub enum Error {
+    /// A file could not be read, written or locked.
+    Io {
+        action: &'static str,
+        path: PathBuf,
+        source: io::Error,
+    },
+    /// A SQLite task database could not be opened, read or written.
+    Database {
+        path: PathBuf,
+        source: rusqlite::Error,
+    },
+    /// A task file, journal or edited document is malformed.
+    Parse {
+        location: String,
+        line: usize,
+        column: Option<usize>,
+        message: String,
+    },
+    /// No task has the given ID.
+    NotFound(usize),
+    /// The command does not make sense for the tasks as they are.
+    Validation(String),
+    /// The command line could not be understood, with clap's message, which
+    /// already starts with `error:` and ends with the usage.
+    Usage(String),
+}
+
+impl Error {
+    pub fn io(action: &'static str, path: &Path, source: io::Error) -> Error {
+        Error::Io {
+            action,
+            path: path.to_path_buf(),
+            source,
+        }
+    }
+
+    pub fn database(path: &Path, source: rusqlite::Error) -> Error {
+        Error::Database {
+            path: path.to_path_buf(),
+            source,
+        }
+    }
+
+    /// Wraps a JSON error from reading `path`, keeping its line and column.
+    /// Errors found in an already parsed document have no position; their
+    /// line is 0.
+    pub fn json(path: &Path, source: serde_json::Error) -> Error {
+        if source.is_io() {
+            return Error::io("read", path, source.into());
+        }
+        let position = format!(" at line {} column {}", source.line(), source.column());
+        let message = source.to_string();
+        Error::Parse {
+            location: path.display().to_string(),
+            line: source.line(),
+            column: Some(source.column()),
+            message: message.trim_end_matches(&position).to_string(),
+        }
+    }
+
+    /// Wraps a TOML error from reading `path`, keeping its line and column.
+    pub fn toml(path: &Path, source: toml::de::Error) -> Error {
+        let message = source.to_string();
+        let message = match message.rfind(" at line ") {
+            Some(end) if source.line_col().is_some() => message[..end].to_string(),
+            _ => message,
+        };
+        let (line, column) = source.line_col().unwrap_or((0, 0));
+        Error::Parse {
+            location: path.display().to_string(),
+            line: line + 1,
+            column: Some(column + 1),
+            message,
+        }
+    }
+
+    /// Wraps an error from parsing the command line. Requests for help or
+    /// the version are not failures, so those are printed and exit here.
+    pub fn usage(source: structopt::clap::Error) -> Error {
+        use structopt::clap::ErrorKind;
+        match source.kind {
+            ErrorKind::HelpDisplayed | ErrorKind::VersionDisplayed => source.exit(),
+            _ => Error::Usage(source.message),
+        }
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Io { .. } | Error::Database { .. } => 74,
+            Error::Parse { .. } => 65,
+            Error::NotFound(_) => 3,
+            Error::Validation(_) => 4,
+            Error::Usage(_) => 64,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io {
+                action,
+                path,
+                source,
+            } => write!(f, "Could not {} {}: {}", action, path.display(), source),
+            Error::Database { path, source } => {
+                write!(f, "Could not use database {}: {}", path.display(), source)
+            }
+            Error::Parse {
+                location,
+                line: 0,
+                message,
+                ..
+            } => write!(f, "{}: {}", location, message),
+            Error::Parse {
+                location,
+                line,
+                column: Some(column),
+                message,
+            } => write!(f, "{}:{}:{}: {}", location, line, column, message),
+            Error::Parse {
+                location,
+                line,
+                column: None,
+                message,
+            } => write!(f, "{}:{}: {}", location, line, message),
+            Error::NotFound(id) => write!(f, "No task with ID {}", id),
+            Error::Validation(message) | Error::Usage(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            Error::Database { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// A value from the command line, a filter or an edited document that could
+/// not be understood.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    what: &'static str,
+    input: String,
+}
+
+impl ParseError {
+    pub fn new(what: &'static str, input: &str) -> ParseError {
+        ParseError {
+            what,
+            input: input.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Could not parse {} `{}`", self.what, self.input)
+    }
+}
+
+impl std::error::Error for ParseError {}
@@ -0,0 +1,651 @@
+use crate::error::ParseError;
+use crate::sqlite::timestamp;
+use crate::status::Status;
+use crate::task::{self, Priority, Task};
+use crate::{due, links, project};
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// A parsed `--filter` query.
+///
+/// Terms are combined with `and`, `or`, `not` and parentheses; terms written
+/// next to each other are implicitly joined with `and`. The supported terms
+/// are:
+///
+/// - `status:pending`, `status:in-progress`, `status:blocked`, `status:waiting`,
+///   `status:completed`, `status:cancelled`, or just `pending`, `completed`
+/// - `status:open` for any state but completed or cancelled, `status:closed`
+///   for those two
+/// - `overdue`, for pending tasks past their due date
+/// - `blocked`, for tasks with the blocked status or waiting on an open
+///   dependency; `ready` for open tasks that are neither blocked nor waiting
+/// - `tag:work` or `+work`; `-+work` is shorthand for `not +work`
+/// - `project:infra`, matching sub-projects such as `infra.ci` as well
+/// - `priority:high` (or `h`, `medium`, `low`, `none`)
+/// - `due.before:friday`, `due.after:"in 7 days"`, `due:none`, `due:any`
+/// - `description:text` or a bare word, matching a case-insensitive substring
+/// - `description.regex:pattern`
+/// - `none` or `all`, matching every task
+///
+/// Values containing spaces or parentheses can be wrapped in double quotes.
+#[derive(Debug)]
+pub struct Filter {
+    source: String,
+    expr: Expr,
+}
+
+#[derive(Debug)]
+enum Expr {
+    All,
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Status(Status),
+    Open(bool),
+    Overdue,
+    Blocked,
+    Ready,
+    Tag(String),
+    Project(String),
+    Priority(Option<Priority>),
+    DueBefore(DateTime<Utc>),
+    DueAfter(DateTime<Utc>),
+    HasDue(bool),
+    Description(String),
+    Regex(Regex),
+}
+
+/// A filter that could not be parsed, pointing at the offending token.
+#[derive(Debug, PartialEq)]
+pub struct QueryError {
+    query: String,
+    position: usize,
+    token: String,
+    message: String,
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let column = self.query[..self.position].chars().count();
+        let width = self.token.chars().count().max(1);
+        if self.token.is_empty() {
+            writeln!(f, "{} at end of filter", self.message)?;
+        } else {
+            writeln!(f, "{} at `{}`", self.message, self.token)?;
+        }
+        writeln!(f, "    {}", self.query)?;
+        write!(f, "    {}{}", " ".repeat(column), "^".repeat(width))
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+impl FromStr for Filter {
+    type Err = QueryError;
+    fn from_str(query: &str) -> Result<Self, Self::Err> {
+        let tokens = tokenize(query)?;
+        let mut parser = Parser {
+            query,
+            tokens,
+            next: 0,
+        };
+        let expr = if parser.tokens.is_empty() {
+            Expr::All
+        } else {
+            parser.expr()?
+        };
+        if let Some(token) = parser.peek() {
+            return Err(parser.error(token, "Unexpected token"));
+        }
+        Ok(Filter {
+            source: query.trim().to_string(),
+            expr,
+        })
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.source.is_empty() {
+            write!(f, "none")
+        } else {
+            write!(f, "{}", self.source)
+        }
+    }
+}
+
+impl Filter {
+    /// Whether `task`, one of `tasks`, passes the filter. The rest of the
+    /// list is needed to tell whether its dependencies are open.
+    pub fn matches(&self, task: &Task, tasks: &BTreeMap<usize, Task>, now: DateTime<Utc>) -> bool {
+        self.expr.matches(task, tasks, now)
+    }
+
+    /// Translates the filter into a condition on the SQLite `tasks` table,
+    /// with its parameters. The condition accepts at least every task
+    /// `matches` does; terms that cannot be translated, such as description
+    /// searches, are left for `matches` to check. Returns `None` if no part
+    /// of the filter can be translated.
+    pub fn to_sql(&self, now: DateTime<Utc>) -> Option<Sql> {
+        self.expr.superset_sql(now)
+    }
+}
+
+/// A SQL condition and the values of its `?` parameters.
+pub type Sql = (String, Vec<String>);
+
+fn join_sql(a: Sql, operator: &str, b: Sql) -> Sql {
+    let (mut values, more) = (a.1, b.1);
+    values.extend(more);
+    (format!("({}) {} ({})", a.0, operator, b.0), values)
+}
+
+/// The statuses `Status::is_open` rejects.
+const CLOSED_SQL: &str = "status IN ('completed', 'cancelled', 'recurring')";
+
+impl Expr {
+    fn matches(&self, task: &Task, tasks: &BTreeMap<usize, Task>, now: DateTime<Utc>) -> bool {
+        match self {
+            Expr::All => true,
+            Expr::And(a, b) => a.matches(task, tasks, now) && b.matches(task, tasks, now),
+            Expr::Or(a, b) => a.matches(task, tasks, now) || b.matches(task, tasks, now),
+            Expr::Not(a) => !a.matches(task, tasks, now),
+            Expr::Status(status) => task.status == *status,
+            Expr::Open(open) => task.status.is_open() == *open,
+            Expr::Overdue => task.is_overdue(now),
+            Expr::Blocked => links::is_blocked(task, tasks),
+            Expr::Ready => {
+                task.status.is_open()
+                    && task.status != Status::Waiting
+                    && !links::is_blocked(task, tasks)
+            }
+            Expr::Tag(tag) => task.tags.contains(tag),
+            Expr::Project(p) => task
+                .project
+                .as_deref()
+                .is_some_and(|project| project::contains(p, project)),
+            Expr::Priority(priority) => task.priority == *priority,
+            Expr::DueBefore(time) => task.due.is_some_and(|due| due < *time),
+            Expr::DueAfter(time) => task.due.is_some_and(|due| due > *time),
+            Expr::HasDue(has) => task.due.is_some() == *has,
+            Expr::Description(text) => task.description.to_lowercase().contains(text),
+            Expr::Regex(re) => re.is_match(&task.description),
+        }
+    }
+}
+
+impl Expr {
+    /// A condition accepting at least the tasks this expression matches.
+    fn superset_sql(&self, now: DateTime<Utc>) -> Option<Sql> {
+        match self {
+            Expr::And(a, b) => match (a.superset_sql(now), b.superset_sql(now)) {
+                (Some(a), Some(b)) => Some(join_sql(a, "AND", b)),
+                (a, b) => a.or(b),
+            },
+            Expr::Or(a, b) => Some(join_sql(a.superset_sql(now)?, "OR", b.superset_sql(now)?)),
+            _ => self.exact_sql(now),
+        }
+    }
+
+    /// A condition accepting exactly the tasks this expression matches. Each
+    /// term is wrapped in `IFNULL` so that a missing value counts as false,
+    /// as it does in `matches`, even under `NOT`.
+    fn exact_sql(&self, now: DateTime<Utc>) -> Option<Sql> {
+        let (condition, values) = match self {
+            Expr::All => ("1".to_string(), vec![]),
+            Expr::And(a, b) => return Some(join_sql(a.exact_sql(now)?, "AND", b.exact_sql(now)?)),
+            Expr::Or(a, b) => return Some(join_sql(a.exact_sql(now)?, "OR", b.exact_sql(now)?)),
+            Expr::Not(a) => {
+                let (condition, values) = a.exact_sql(now)?;
+                return Some((format!("NOT ({})", condition), values));
+            }
+            Expr::Status(status) => ("status = ?".to_string(), vec![status.to_string()]),
+            Expr::Open(true) => (format!("NOT {}", CLOSED_SQL), vec![]),
+            Expr::Open(false) => (CLOSED_SQL.to_string(), vec![]),
+            Expr::Overdue => (
+                format!("NOT {} AND due < ?", CLOSED_SQL),
+                vec![timestamp(now)],
+            ),
+            Expr::Tag(tag) => (
+                "id IN (SELECT task_id FROM tags WHERE tag = ?)".to_string(),
+                vec![tag.clone()],
+            ),
+            Expr::Project(p) => (
+                "project = ? OR instr(project, ?) = 1".to_string(),
+                vec![p.clone(), format!("{}.", p)],
+            ),
+            Expr::Priority(Some(p)) => ("priority = ?".to_string(), vec![(*p as i64).to_string()]),
+            Expr::Priority(None) => ("priority IS NULL".to_string(), vec![]),
+            Expr::DueBefore(time) => ("due < ?".to_string(), vec![timestamp(*time)]),
+            Expr::DueAfter(time) => ("due > ?".to_string(), vec![timestamp(*time)]),
+            Expr::HasDue(true) => ("due IS NOT NULL".to_string(), vec![]),
+            Expr::HasDue(false) => ("due IS NULL".to_string(), vec![]),
+            // Dependencies are only stored in `data`.
+            Expr::Blocked | Expr::Ready => return None,
+            Expr::Description(_) | Expr::Regex(_) => return None,
+        };
+        Some((format!("IFNULL(({}), 0)", condition), values))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Kind {
+    LParen,
+    RParen,
+    Word,
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: Kind,
+    /// The word with any quotes removed.
+    text: String,
+    /// Byte offset and length of the token as written in the query.
+    position: usize,
+    len: usize,
+}
+
+fn tokenize(query: &str) -> Result<Vec<Token>, QueryError> {
+    let mut tokens = Vec::new();
+    let mut chars = query.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        if c == '(' || c == ')' {
+            chars.next();
+            tokens.push(Token {
+                kind: if c == '(' { Kind::LParen } else { Kind::RParen },
+                text: c.to_string(),
+                position: start,
+                len: 1,
+            });
+            continue;
+        }
+        let mut text = String::new();
+        let mut end = start;
+        let mut quoted = false;
+        while let Some(&(i, c)) = chars.peek() {
+            if !quoted && (c.is_whitespace() || c == '(' || c == ')') {
+                break;
+            }
+            chars.next();
+            end = i + c.len_utf8();
+            if c == '"' {
+                quoted = !quoted;
+            } else {
+                text.push(c);
+            }
+        }
+        if quoted {
+            return Err(QueryError {
+                query: query.to_string(),
+                position: start,
+                token: query[start..end].to_string(),
+                message: "Unterminated quote".to_string(),
+            });
+        }
+        tokens.push(Token {
+            kind: Kind::Word,
+            text,
+            position: start,
+            len: end - start,
+        });
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    query: &'a str,
+    tokens: Vec<Token>,
+    next: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<Token> {
+        self.tokens.get(self.next).cloned()
+    }
+
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        self.peek()
+            .is_some_and(|t| t.kind == Kind::Word && t.text.eq_ignore_ascii_case(keyword))
+    }
+
+    fn error(&self, token: Token, message: &str) -> QueryError {
+        QueryError {
+            query: self.query.to_string(),
+            position: token.position,
+            token: self.query[token.position..token.position + token.len].to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    fn end_error(&self, message: &str) -> QueryError {
+        QueryError {
+            query: self.query.to_string(),
+            position: self.query.len(),
+            token: String::new(),
+            message: message.to_string(),
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr, QueryError> {
+        let mut expr = self.and()?;
+        while self.peek_keyword("or") {
+            self.next += 1;
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, QueryError> {
+        let mut expr = self.unary()?;
+        loop {
+            if self.peek_keyword("and") {
+                self.next += 1;
+            } else if self.peek_keyword("or") || self.peek().is_none_or(|t| t.kind == Kind::RParen)
+            {
+                return Ok(expr);
+            }
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, QueryError> {
+        if self.peek_keyword("not") {
+            self.next += 1;
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        let token = self
+            .peek()
+            .ok_or_else(|| self.end_error("Expected a filter term"))?;
+        self.next += 1;
+        match token.kind {
+            Kind::LParen => {
+                let expr = self.expr()?;
+                match self.peek() {
+                    Some(t) if t.kind == Kind::RParen => {
+                        self.next += 1;
+                        Ok(expr)
+                    }
+                    Some(t) => Err(self.error(t, "Expected `)`")),
+                    None => Err(self.error(token, "Unclosed `(`")),
+                }
+            }
+            Kind::RParen => Err(self.error(token, "Unexpected `)`")),
+            Kind::Word if ["and", "or"].contains(&token.text.to_lowercase().as_str()) => {
+                Err(self.error(token, "Expected a filter term"))
+            }
+            Kind::Word => self.term(token),
+        }
+    }
+
+    fn term(&self, token: Token) -> Result<Expr, QueryError> {
+        let text = token.text.as_str();
+        let invalid = |e: ParseError| self.error(token.clone(), &e.to_string());
+        if let Some(tag) = text.strip_prefix("-+") {
+            let tag = task::parse_tag(tag).map_err(invalid)?;
+            return Ok(Expr::Not(Box::new(Expr::Tag(tag))));
+        }
+        if text.starts_with('+') {
+            return task::parse_tag(text).map(Expr::Tag).map_err(invalid);
+        }
+        let (field, value) = match text.split_once(':') {
+            Some(pair) => pair,
+            None => {
+                return Ok(match text.to_lowercase().as_str() {
+                    "none" | "all" => Expr::All,
+                    "pending" => Expr::Status(Status::Pending),
+                    "completed" => Expr::Status(Status::Completed),
+                    "overdue" => Expr::Overdue,
+                    "blocked" => Expr::Blocked,
+                    "ready" => Expr::Ready,
+                    word => Expr::Description(word.to_string()),
+                })
+            }
+        };
+        match field.to_lowercase().as_str() {
+            "status" => match value.to_lowercase().as_str() {
+                "open" => Ok(Expr::Open(true)),
+                "closed" => Ok(Expr::Open(false)),
+                "overdue" => Ok(Expr::Overdue),
+                _ => value.parse().map(Expr::Status).map_err(invalid),
+            },
+            "tag" => task::parse_tag(value).map(Expr::Tag).map_err(invalid),
+            "project" => project::parse(value).map(Expr::Project).map_err(invalid),
+            "priority" => match value.to_lowercase().as_str() {
+                "none" => Ok(Expr::Priority(None)),
+                _ => value
+                    .parse()
+                    .map(|p| Expr::Priority(Some(p)))
+                    .map_err(invalid),
+            },
+            "due" => match value.to_lowercase().as_str() {
+                "none" => Ok(Expr::HasDue(false)),
+                "any" => Ok(Expr::HasDue(true)),
+                _ => Err(self.error(
+                    token.clone(),
+                    "Expected due:none, due:any, due.before or due.after",
+                )),
+            },
+            "due.before" => due::parse(value).map(Expr::DueBefore).map_err(invalid),
+            "due.after" => due::parse(value).map(Expr::DueAfter).map_err(invalid),
+            "description" | "desc" => Ok(Expr::Description(value.to_lowercase())),
+            "description.regex" | "desc.regex" => Regex::new(value)
+                .map(Expr::Regex)
+                .map_err(|e| self.error(token.clone(), &format!("Invalid regex ({})", e))),
+            _ => Err(self.error(token.clone(), "Unknown filter field")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sqlite::Database;
+    use crate::storage::Storage;
+    use crate::TaskList;
+    use chrono::Duration;
+    use rusqlite::{params_from_iter, Connection};
+    use std::collections::BTreeSet;
+
+    fn error(query: &str) -> (usize, String, String) {
+        let e = query.parse::<Filter>().expect_err(query);
+        (e.position, e.token, e.message)
+    }
+
+    #[test]
+    fn errors_point_at_the_offending_token() {
+        let cases = [
+            ("status:bogus", 0, "status:bogus"),
+            ("pending and status:bogus", 12, "status:bogus"),
+            ("+work nope:x", 6, "nope:x"),
+            ("due:monday", 0, "due:monday"),
+            ("due.before:\"next year\"", 0, "due.before:\"next year\""),
+            ("desc.regex:\"(\"", 0, "desc.regex:\"(\""),
+            ("desc.regex:(", 12, ""),
+            ("pending )", 8, ")"),
+            ("(pending +work", 0, "("),
+            ("(pending +work or", 17, ""),
+            ("pending and", 11, ""),
+            ("or pending", 0, "or"),
+            ("tag:work and \"un closed", 13, "\"un closed"),
+            ("(+x) ( +y +z", 5, "("),
+        ];
+        for (query, position, token) in cases {
+            let (at, found, message) = error(query);
+            assert_eq!(
+                (at, found.as_str()),
+                (position, token),
+                "{}: {}",
+                query,
+                message
+            );
+        }
+    }
+
+    #[test]
+    fn documented_terms_parse() {
+        let terms = [
+            "status:in-progress",
+            "completed",
+            "status:open",
+            "overdue",
+            "blocked",
+            "ready",
+            "tag:work",
+            "-+work",
+            "project:infra",
+            "priority:h",
+            "priority:none",
+            "due.before:friday",
+            "due.after:\"in 7 days\"",
+            "due:none",
+            "due:any",
+            "description:text",
+            "description.regex:pattern",
+            "all",
+            "status:pending and (tag:work or due.before:friday)",
+        ];
+        for term in terms {
+            assert!(term.parse::<Filter>().is_ok(), "{}", term);
+        }
+    }
+
+    #[test]
+    fn error_display_underlines_the_token() {
+        let e = "pending and status:bogus".parse::<Filter>().unwrap_err();
+        let shown = e.to_string();
+        let lines: Vec<&str> = shown.lines().collect();
+        assert!(lines[0].ends_with("at `status:bogus`"), "{}", shown);
+        assert_eq!(lines[1], "    pending and status:bogus");
+        assert_eq!(
+            lines[2],
+            format!("    {}{}", " ".repeat(12), "^".repeat(12))
+        );
+    }
+
+    fn fixture(now: DateTime<Utc>) -> TaskList {
+        let mut list = TaskList::default();
+        let mut add = |description: &str, edit: &dyn Fn(&mut Task)| {
+            let mut task = Task::new(description.to_string());
+            task.id = list.next_id;
+            edit(&mut task);
+            list.tasks.insert(task.id, task);
+            list.next_id += 1;
+        };
+        add("Write report", &|t| {
+            t.tags.insert("work".to_string());
+            t.project = Some("infra".to_string());
+            t.priority = Some(Priority::High);
+            t.due = Some(now - Duration::days(1));
+        });
+        add("Buy milk", &|t| {
+            t.status = Status::Completed;
+            t.tags.insert("home".to_string());
+            t.due = Some(now + Duration::days(2));
+        });
+        add("Deploy CI", &|t| {
+            t.status = Status::InProgress;
+            t.project = Some("infra.ci".to_string());
+            t.priority = Some(Priority::Low);
+            t.due = Some(now + Duration::days(10));
+        });
+        add("Review report", &|t| {
+            t.tags.extend(["work".to_string(), "review".to_string()]);
+            t.depends_on.insert(3);
+        });
+        add("Plan trip", &|t| {
+            t.status = Status::Waiting;
+            t.project = Some("infrastructure".to_string());
+        });
+        add("Old chore", &|t| {
+            t.status = Status::Cancelled;
+            t.priority = Some(Priority::Medium);
+            t.due = Some(now - Duration::days(5));
+        });
+        list
+    }
+
+    fn in_memory(filter: &Filter, list: &TaskList, now: DateTime<Utc>) -> BTreeSet<usize> {
+        list.tasks
+            .values()
+            .filter(|task| filter.matches(task, &list.tasks, now))
+            .map(|task| task.id)
+            .collect()
+    }
+
+    /// The SQLite backend narrows `ls` with `to_sql` and then applies the
+    /// filter; both steps together must give what the JSON backend gives.
+    #[test]
+    fn sqlite_agrees_with_matches() {
+        let now = Utc::now();
+        let list = fixture(now);
+        let path = std::env::temp_dir().join(format!("todo-filter-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let database = Database::open(&path).unwrap();
+        database.save(&TaskList::default(), &list).unwrap();
+        let conn = Connection::open(&path).unwrap();
+        let exact = [
+            "status:pending",
+            "status:open",
+            "status:closed",
+            "overdue",
+            "+work",
+            "-+work",
+            "tag:home or project:infra",
+            "project:infra",
+            "priority:high",
+            "priority:none",
+            "not priority:low",
+            "due.before:tomorrow",
+            "due.after:today",
+            "due:none",
+            "not due.before:tomorrow",
+            "status:pending and (tag:work or due.before:friday)",
+            "not (+work or status:closed)",
+        ];
+        let partial = [
+            "blocked",
+            "ready",
+            "pending and blocked",
+            "report or +home",
+            "pending and desc:report",
+            "description.regex:^(Buy|Plan)",
+            "not blocked and status:open",
+        ];
+        for query in exact.iter().chain(&partial) {
+            let filter: Filter = query.parse().unwrap();
+            let expected = in_memory(&filter, &list, now);
+
+            let loaded = database.load_matching(&[filter], now).unwrap();
+            let filter: Filter = query.parse().unwrap();
+            let found = in_memory(&filter, &loaded, now);
+            assert_eq!(found, expected, "{}", query);
+
+            let (condition, values) = match filter.to_sql(now) {
+                Some(sql) => sql,
+                None => continue,
+            };
+            let sql = format!("SELECT id FROM tasks WHERE {}", condition);
+            let mut statement = conn.prepare(&sql).unwrap();
+            let selected: BTreeSet<usize> = statement
+                .query_map(params_from_iter(&values), |row| row.get::<_, i64>(0))
+                .unwrap()
+                .map(|id| id.unwrap() as usize)
+                .collect();
+            if exact.contains(query) {
+                assert_eq!(selected, expected, "{}", query);
+            } else {
+                assert!(selected.is_superset(&expected), "{}", query);
+            }
+        }
+        drop(conn);
+        drop(database);
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,325 @@
+use crate::atomic;
+use crate::error::Error;
+use crate::task::Task;
+use crate::TaskList;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+/// Entries kept for undo; older ones are dropped as new ones are recorded.
+const LIMIT: usize = 100;
+
+/// The undo and redo history for one task file, stored beside it as
+/// `<name>.journal.json`.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Journal {
+    undo: Vec<Entry>,
+    redo: Vec<Entry>,
+}
+
+/// One mutating command, recorded as the full before and after state of
+/// every task it touched so it can be reversed or reapplied exactly.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Entry {
+    pub at: DateTime<Utc>,
+    pub command: String,
+    changes: Vec<Change>,
+    /// For one half of a move, the other task file the command changed. Its
+    /// journal holds the other half, recorded at the same time, and the two
+    /// halves are only undone or redone together.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub linked: Option<PathBuf>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct Change {
+    id: usize,
+    before: Option<Task>,
+    after: Option<Task>,
+}
+
+pub fn path_for(tasks_path: &Path) -> PathBuf {
+    let stem = tasks_path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    tasks_path.with_file_name(format!("{}.journal.json", stem))
+}
+
+/// `path` made absolute, so that links between journals hold wherever
+/// `todo` is run from.
+pub fn absolute(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+impl Journal {
+    pub fn load(path: &Path) -> Result<Journal, Error> {
+        match File::open(path) {
+            Ok(file) => {
+                serde_json::from_reader(BufReader::new(file)).map_err(|e| Error::json(path, e))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Journal::default()),
+            Err(e) => Err(Error::io("read", path, e)),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        let contents = serde_json::to_vec(self).expect("journal serializes to JSON");
+        atomic::write(path, &contents).map_err(|e| Error::io("write", path, e))
+    }
+
+    /// Records the difference between two states of the task list. Returns
+    /// false, recording nothing, if the command changed no task.
+    pub fn record(&mut self, command: String, before: &TaskList, after: &TaskList) -> bool {
+        let ids = before.tasks.keys().chain(after.tasks.keys());
+        let mut changes: Vec<Change> = ids
+            .filter(|id| !same(before.tasks.get(id), after.tasks.get(id)))
+            .map(|id| Change {
+                id: *id,
+                before: before.tasks.get(id).cloned(),
+                after: after.tasks.get(id).cloned(),
+            })
+            .collect();
+        changes.sort_by_key(|c| c.id);
+        changes.dedup_by_key(|c| c.id);
+        if changes.is_empty() {
+            return false;
+        }
+        self.undo.push(Entry {
+            at: Utc::now(),
+            command,
+            changes,
+            linked: None,
+        });
+        if self.undo.len() > LIMIT {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+        true
+    }
+
+    /// Marks the newest entry as one half of a command that also changed the
+    /// task file `other`, whose half was recorded at `at`, and returns `at`.
+    /// Without `at`, the newest entry's own time is kept.
+    pub fn link_latest(&mut self, other: &Path, at: Option<DateTime<Utc>>) -> DateTime<Utc> {
+        let entry = self.undo.last_mut().expect("an entry was just recorded");
+        entry.linked = Some(absolute(other));
+        if let Some(at) = at {
+            entry.at = at;
+        }
+        entry.at
+    }
+
+    /// The entry the next undo, or the next redo if `redo`, would apply.
+    pub fn next(&self, redo: bool) -> Option<&Entry> {
+        if redo {
+            self.redo.last()
+        } else {
+            self.undo.last()
+        }
+    }
+
+    /// The other task files changed along with the next `count` entries to
+    /// undo, or to redo if `redo`.
+    pub fn linked(&self, redo: bool, count: usize) -> Vec<PathBuf> {
+        let stack = if redo { &self.redo } else { &self.undo };
+        stack
+            .iter()
+            .rev()
+            .take(count)
+            .filter_map(|entry| entry.linked.clone())
+            .collect()
+    }
+
+    /// Reverses up to `count` of the most recent entries, newest first.
+    pub fn undo(&mut self, tasks: &mut TaskList, count: usize) -> Result<Vec<Entry>, Error> {
+        let mut undone = Vec::new();
+        for _ in 0..count {
+            let entry = match self.undo.pop() {
+                Some(entry) => entry,
+                None => break,
+            };
+            if let Err(e) = entry.apply(tasks, true) {
+                self.undo.push(entry);
+                return Err(e);
+            }
+            self.redo.push(entry.clone());
+            undone.push(entry);
+        }
+        Ok(undone)
+    }
+
+    /// Reapplies up to `count` of the most recently undone entries.
+    pub fn redo(&mut self, tasks: &mut TaskList, count: usize) -> Result<Vec<Entry>, Error> {
+        let mut redone = Vec::new();
+        for _ in 0..count {
+            let entry = match self.redo.pop() {
+                Some(entry) => entry,
+                None => break,
+            };
+            if let Err(e) = entry.apply(tasks, false) {
+                self.redo.push(entry);
+                return Err(e);
+            }
+            self.undo.push(entry.clone());
+            redone.push(entry);
+        }
+        Ok(redone)
+    }
+}
+
+impl Entry {
+    /// Moves every touched task from one recorded state to the other. The
+    /// tasks must still be exactly as recorded, so an entry is never applied
+    /// over changes made outside the journal. `next_id` is left alone, so
+    /// undoing an add never frees its ID for reuse.
+    fn apply(&self, tasks: &mut TaskList, reverse: bool) -> Result<(), Error> {
+        let pick = |change: &Change| {
+            if reverse {
+                (change.after.clone(), change.before.clone())
+            } else {
+                (change.before.clone(), change.after.clone())
+            }
+        };
+        for change in &self.changes {
+            let (expected, _) = pick(change);
+            if !same(tasks.tasks.get(&change.id), expected.as_ref()) {
+                return Err(Error::Validation(format!(
+                    "Task {} has changed since `{}`; it cannot be applied",
+                    change.id, self.command
+                )));
+            }
+        }
+        for change in &self.changes {
+            match pick(change).1 {
+                Some(task) => tasks.tasks.insert(change.id, task),
+                None => tasks.tasks.remove(&change.id),
+            };
+        }
+        Ok(())
+    }
+}
+
+/// Compares tasks by every stored field, unlike `Task`'s `PartialEq`, which
+/// only compares creation times.
+fn same(a: Option<&Task>, b: Option<&Task>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => serde_json::to_value(a).ok() == serde_json::to_value(b).ok(),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list(descriptions: &[&str]) -> TaskList {
+        let mut list = TaskList::default();
+        for description in descriptions {
+            let mut task = Task::new(description.to_string());
+            task.id = list.next_id;
+            list.tasks.insert(task.id, task);
+            list.next_id += 1;
+        }
+        list
+    }
+
+    fn descriptions(list: &TaskList) -> Vec<(usize, &str)> {
+        list.tasks
+            .values()
+            .map(|task| (task.id, task.description.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn undo_of_rm_restores_the_task_exactly() {
+        let mut journal = Journal::default();
+        let before = list(&["keep", "remove", "last"]);
+        let mut tasks = before.clone();
+        tasks.tasks.remove(&2);
+        assert!(journal.record("rm 2".to_string(), &before, &tasks));
+
+        let undone = journal.undo(&mut tasks, 1).unwrap();
+        assert_eq!(undone.len(), 1);
+        assert_eq!(undone[0].command, "rm 2");
+        assert!(same(tasks.tasks.get(&2), before.tasks.get(&2)));
+        assert_eq!(tasks.tasks[&2].created, before.tasks[&2].created);
+        assert_eq!(descriptions(&tasks), descriptions(&before));
+        assert_eq!(tasks.next_id, 4);
+
+        journal.redo(&mut tasks, 1).unwrap();
+        assert_eq!(descriptions(&tasks), [(1, "keep"), (3, "last")]);
+    }
+
+    #[test]
+    fn nothing_is_recorded_without_changes() {
+        let mut journal = Journal::default();
+        let tasks = list(&["one"]);
+        assert!(!journal.record("ls".to_string(), &tasks, &tasks.clone()));
+        assert!(journal.undo(&mut tasks.clone(), 1).unwrap().is_empty());
+    }
+
+    #[test]
+    fn refuses_entries_for_tasks_changed_outside_the_journal() {
+        let mut journal = Journal::default();
+        let before = list(&["one", "two"]);
+        let mut tasks = before.clone();
+        tasks.tasks.get_mut(&1).unwrap().description = "first".to_string();
+        tasks.tasks.get_mut(&2).unwrap().description = "second".to_string();
+        journal.record("edit".to_string(), &before, &tasks);
+
+        tasks.tasks.get_mut(&2).unwrap().description = "by hand".to_string();
+        let untouched = tasks.clone();
+        match journal.undo(&mut tasks, 1) {
+            Err(Error::Validation(message)) => {
+                assert_eq!(
+                    message,
+                    "Task 2 has changed since `edit`; it cannot be applied"
+                )
+            }
+            other => panic!("expected a validation error, got {:?}", other),
+        }
+        assert_eq!(descriptions(&tasks), descriptions(&untouched));
+        assert_eq!(journal.undo.len(), 1, "the entry stays for a later undo");
+        assert!(journal.redo.is_empty());
+    }
+
+    #[test]
+    fn a_new_entry_clears_redo() {
+        let mut journal = Journal::default();
+        let empty = list(&[]);
+        let one = list(&["one"]);
+        let mut tasks = one.clone();
+        journal.record("add one".to_string(), &empty, &one);
+        journal.undo(&mut tasks, 1).unwrap();
+        assert_eq!(journal.redo.len(), 1);
+
+        let other = list(&["other"]);
+        journal.record("add other".to_string(), &tasks, &other);
+        assert!(journal.redo.is_empty());
+        let mut tasks = other;
+        assert!(journal.redo(&mut tasks, 1).unwrap().is_empty());
+        assert_eq!(descriptions(&tasks), [(1, "other")]);
+    }
+
+    #[test]
+    fn keeps_only_the_latest_entries() {
+        let mut journal = Journal::default();
+        let mut tasks = list(&["task"]);
+        for n in 0..LIMIT + 5 {
+            let before = tasks.clone();
+            tasks.tasks.get_mut(&1).unwrap().description = n.to_string();
+            journal.record(format!("edit {}", n), &before, &tasks);
+        }
+        assert_eq!(journal.undo.len(), LIMIT);
+        assert_eq!(journal.undo[0].command, "edit 5");
+
+        let undone = journal.undo(&mut tasks, LIMIT + 5).unwrap();
+        assert_eq!(undone.len(), LIMIT);
+        assert_eq!(undone[0].command, format!("edit {}", LIMIT + 4));
+        assert_eq!(tasks.tasks[&1].description, "4");
+    }
+}
@@ -0,0 +1,231 @@
+use crate::error::Error;
+use crate::status::Status;
+use crate::task::Task;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Whether `task` is waiting on something: either its status says so, or
+/// one of the tasks it depends on is still open. Dependencies that have
+/// been removed no longer hold it up.
+pub fn is_blocked(task: &Task, tasks: &BTreeMap<usize, Task>) -> bool {
+    task.status == Status::Blocked || (task.status.is_open() && has_open_dependencies(task, tasks))
+}
+
+/// Whether any task `task` depends on is still open.
+pub fn has_open_dependencies(task: &Task, tasks: &BTreeMap<usize, Task>) -> bool {
+    task.depends_on
+        .iter()
+        .filter_map(|id| tasks.get(id))
+        .any(|dependency| dependency.status.is_open())
+}
+
+/// The open subtasks of task `id`, at any depth, deepest first, so that
+/// completing them in order never completes a parent before its children.
+pub fn open_subtasks(id: usize, tasks: &BTreeMap<usize, Task>) -> Vec<usize> {
+    let mut found = Vec::new();
+    for task in tasks.values().filter(|t| t.parent == Some(id)) {
+        found.extend(open_subtasks(task.id, tasks));
+        if task.status.is_open() {
+            found.push(task.id);
+        }
+    }
+    found
+}
+
+/// Checks that the parent and dependencies of task `id` exist and that
+/// following either never leads back to it.
+pub fn check(id: usize, tasks: &BTreeMap<usize, Task>) -> Result<(), Error> {
+    let task = tasks.get(&id).ok_or(Error::NotFound(id))?;
+    let linked = task.parent.iter().chain(&task.depends_on);
+    if let Some(missing) = linked.copied().find(|other| !tasks.contains_key(other)) {
+        return Err(Error::NotFound(missing));
+    }
+    let mut ancestor = task.parent;
+    let mut chain = vec![id];
+    // Bounded, so a loop elsewhere in a hand-edited file cannot hang us.
+    while let Some(parent) = ancestor.filter(|_| chain.len() <= tasks.len()) {
+        chain.push(parent);
+        if parent == id {
+            return Err(cycle("is a subtask of", &chain));
+        }
+        ancestor = tasks.get(&parent).and_then(|t| t.parent);
+    }
+    if let Some(path) = dependency_path(id, task, tasks, &mut BTreeSet::new()) {
+        return Err(cycle("depends on", &path));
+    }
+    Ok(())
+}
+
+/// A chain of dependencies from `task` back to `target`, if there is one.
+fn dependency_path(
+    target: usize,
+    task: &Task,
+    tasks: &BTreeMap<usize, Task>,
+    seen: &mut BTreeSet<usize>,
+) -> Option<Vec<usize>> {
+    for &next in &task.depends_on {
+        if next == target {
+            return Some(vec![task.id, target]);
+        }
+        if !seen.insert(next) {
+            continue;
+        }
+        if let Some(mut path) = tasks
+            .get(&next)
+            .and_then(|t| dependency_path(target, t, tasks, seen))
+        {
+            path.insert(0, task.id);
+            return Some(path);
+        }
+    }
+    None
+}
+
+fn cycle(relation: &str, chain: &[usize]) -> Error {
+    let chain = chain
+        .iter()
+        .map(|id| id.to_string())
+        .collect::<Vec<_>>()
+        .join(&format!(" {} ", relation));
+    Error::Validation(format!("That would make a cycle: {}", chain))
+}
+
+/// Removes every link to task `id` from the other tasks, before it leaves
+/// the list. Its subtasks move up to its own parent.
+pub fn detach(id: usize, tasks: &mut BTreeMap<usize, Task>) {
+    let parent = tasks.get(&id).and_then(|t| t.parent);
+    for task in tasks.values_mut() {
+        if task.parent == Some(id) {
+            task.parent = parent;
+        }
+        task.depends_on.remove(&id);
+    }
+}
+
+/// Arranges `listed`, already in listing order, as a forest: each task is
+/// followed by its listed subtasks, one level deeper. Tasks whose parent is
+/// not listed start a tree of their own.
+pub fn tree<'a>(listed: &[&'a Task]) -> Vec<(usize, &'a Task)> {
+    let ids: BTreeSet<usize> = listed.iter().map(|t| t.id).collect();
+    let mut rows = Vec::new();
+    for task in listed {
+        if !task.parent.is_some_and(|p| ids.contains(&p)) {
+            add_subtree(task, 0, listed, &mut rows);
+        }
+    }
+    rows
+}
+
+fn add_subtree<'a>(
+    task: &'a Task,
+    depth: usize,
+    listed: &[&'a Task],
+    rows: &mut Vec<(usize, &'a Task)>,
+) {
+    rows.push((depth, task));
+    for child in listed.iter().filter(|t| t.parent == Some(task.id)) {
+        add_subtree(child, depth + 1, listed, rows);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tasks given as `(id, parent, dependencies)`.
+    fn tasks(links: &[(usize, Option<usize>, &[usize])]) -> BTreeMap<usize, Task> {
+        links
+            .iter()
+            .map(|&(id, parent, depends_on)| {
+                let mut task = Task::new(format!("task {}", id));
+                task.id = id;
+                task.parent = parent;
+                task.depends_on = depends_on.iter().copied().collect();
+                (id, task)
+            })
+            .collect()
+    }
+
+    fn refusal(id: usize, tasks: &BTreeMap<usize, Task>) -> String {
+        match check(id, tasks) {
+            Err(Error::Validation(message)) => message,
+            other => panic!("task {}: expected a cycle, got {:?}", id, other),
+        }
+    }
+
+    #[test]
+    fn accepts_trees_and_shared_dependencies() {
+        let tasks = tasks(&[
+            (1, None, &[]),
+            (2, Some(1), &[]),
+            (3, Some(2), &[1]),
+            (4, None, &[1, 3]),
+        ]);
+        for id in 1..=4 {
+            assert!(check(id, &tasks).is_ok(), "task {}", id);
+        }
+    }
+
+    #[test]
+    fn refuses_missing_links() {
+        let tasks = tasks(&[(1, Some(9), &[]), (2, None, &[1, 8])]);
+        assert!(matches!(check(1, &tasks), Err(Error::NotFound(9))));
+        assert!(matches!(check(2, &tasks), Err(Error::NotFound(8))));
+    }
+
+    #[test]
+    fn refuses_parent_cycles() {
+        let own = tasks(&[(1, Some(1), &[])]);
+        assert_eq!(
+            refusal(1, &own),
+            "That would make a cycle: 1 is a subtask of 1"
+        );
+
+        let chain = tasks(&[(1, Some(3), &[]), (2, Some(1), &[]), (3, Some(2), &[])]);
+        assert_eq!(
+            refusal(1, &chain),
+            "That would make a cycle: 1 is a subtask of 3 is a subtask of 2 is a subtask of 1"
+        );
+    }
+
+    #[test]
+    fn a_parent_loop_elsewhere_does_not_hang() {
+        let tasks = tasks(&[(1, Some(2), &[]), (2, Some(3), &[]), (3, Some(2), &[])]);
+        assert!(check(1, &tasks).is_ok());
+    }
+
+    #[test]
+    fn refuses_dependency_cycles() {
+        let own = tasks(&[(1, None, &[1])]);
+        assert_eq!(refusal(1, &own), "That would make a cycle: 1 depends on 1");
+
+        let chain = tasks(&[
+            (1, None, &[2]),
+            (2, None, &[3]),
+            (3, None, &[1]),
+            (4, None, &[2]),
+        ]);
+        assert_eq!(
+            refusal(1, &chain),
+            "That would make a cycle: 1 depends on 2 depends on 3 depends on 1"
+        );
+        assert!(check(4, &chain).is_ok());
+    }
+
+    #[test]
+    fn open_subtasks_come_deepest_first() {
+        let mut tasks = tasks(&[
+            (1, None, &[]),
+            (2, Some(1), &[]),
+            (3, Some(2), &[]),
+            (4, Some(1), &[]),
+            (5, Some(4), &[]),
+            (6, Some(5), &[]),
+            (7, Some(4), &[]),
+            (8, None, &[]),
+        ]);
+        tasks.get_mut(&7).unwrap().status = Status::Completed;
+        assert_eq!(open_subtasks(1, &tasks), vec![3, 2, 6, 5, 4]);
+        assert_eq!(open_subtasks(4, &tasks), vec![6, 5]);
+        assert!(open_subtasks(8, &tasks).is_empty());
+    }
+}
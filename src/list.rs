@@ -0,0 +1,106 @@
+use crate::error::{Error, ParseError};
+use crate::{deserialize_tasks, storage, TaskList};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Parses a list name. Names become file names in the lists directory, so
+/// they are limited to letters, digits, `-` and `_`.
+pub fn parse(name: &str) -> Result<String, ParseError> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err(ParseError::new("list name", name));
+    }
+    Ok(name.to_string())
+}
+
+/// The directory named lists are kept in, e.g. `~/.local/share/todo/lists`.
+/// Nothing else is kept there, so finding the lists never picks up other
+/// files that happen to sit beside the task file.
+pub fn dir() -> Result<PathBuf, Error> {
+    dirs::data_dir()
+        .map(|dir| dir.join("todo").join("lists"))
+        .ok_or_else(|| Error::Validation("Could not find a data directory for lists".to_string()))
+}
+
+/// The file holding the list `name`. The task file `main` is the list named
+/// after it, usually `todo`; every other list is kept in `dir` with the
+/// same storage backend as `main`.
+pub fn path_for(main: &Path, name: &str) -> Result<PathBuf, Error> {
+    if name == name_of(main) {
+        return Ok(main.to_path_buf());
+    }
+    let extension = main.extension().unwrap_or_else(|| "json".as_ref());
+    Ok(dir()?.join(name).with_extension(extension))
+}
+
+/// The name of the list stored at `path`.
+pub fn name_of(path: &Path) -> String {
+    path.file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Finds the task file `main` and every list in `dir`, skipping the
+/// journals and archives kept beside them.
+fn find(main: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut paths: Vec<PathBuf> = Some(main)
+        .filter(|p| p.exists())
+        .map(Path::to_path_buf)
+        .into_iter()
+        .collect();
+    let dir = dir()?;
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(paths),
+        Err(e) => return Err(Error::io("read", &dir, e)),
+    };
+    for entry in entries {
+        let path = entry.map_err(|e| Error::io("read", &dir, e))?.path();
+        let name = name_of(&path);
+        let is_list = storage::is_task_file(&path) && parse(&name).is_ok() && name != name_of(main);
+        if is_list {
+            paths.push(path);
+        }
+    }
+    paths.sort_by_key(|path| name_of(path));
+    Ok(paths)
+}
+
+/// Prints the task file `main` and every other list with their task counts,
+/// marking `current`, the one in use.
+pub fn print_lists(main: &Path, current: &Path) -> Result<(), Error> {
+    let mut rows: Vec<(String, TaskList)> = Vec::new();
+    for path in find(main)? {
+        rows.push((name_of(&path), deserialize_tasks(&path)?));
+    }
+    let current = name_of(current);
+    if !rows.iter().any(|(name, _)| *name == current) {
+        rows.push((current.clone(), TaskList::default()));
+        rows.sort_by(|a, b| a.0.cmp(&b.0));
+    }
+    let width = rows
+        .iter()
+        .map(|(name, _)| name.chars().count())
+        .max()
+        .unwrap_or(0);
+
+    let header = "Lists";
+    let line_break = (0..header.len()).map(|_| "─").collect::<String>();
+    println!("{}", header);
+    println!("{}", line_break);
+    for (name, list) in rows {
+        let open = list.tasks.values().filter(|t| t.status.is_open()).count();
+        println!(
+            "{} {:width$}  {} open, {} total",
+            if name == current { '*' } else { ' ' },
+            name,
+            open,
+            list.tasks.len(),
+            width = width
+        );
+    }
+    Ok(())
+}
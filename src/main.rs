@@ -1,181 +1,1450 @@
+mod archive;
+mod atomic;
+mod color;
+mod config;
+mod due;
+mod editor;
+mod error;
+mod filter;
+mod journal;
+mod links;
+mod list;
+mod project;
+mod recur;
+mod report;
+mod schema;
+mod search;
+mod show;
+mod sqlite;
+mod status;
+mod storage;
 mod task;
-use crate::task::Task;
+use crate::color::ColorMode;
+use crate::config::Config;
+use crate::error::{Error, ParseError};
+use crate::filter::Filter;
+use crate::journal::Journal;
+use crate::recur::Recurrence;
+use crate::status::{Status, TransitionError};
+use crate::task::{Changes, Priority, Task};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde::Serialize;
+use std::cmp::Ordering;
 use std::collections::BTreeMap;
-use std::error::Error;
-use std::fmt;
-use std::fs::File;
-use std::fs::OpenOptions;
-use std::path::PathBuf;
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use structopt::StructOpt;
 
-type ParseError = &'static str;
-
 #[derive(Debug)]
-enum Operation {
-    Add,
-    Remove,
-    Complete,
-    List,
+enum Sort {
+    Id,
+    Priority,
+    Due,
 }
 
-impl FromStr for Operation {
+impl FromStr for Sort {
     type Err = ParseError;
-    fn from_str(filter: &str) -> Result<Self, Self::Err> {
-        match filter {
-            "add" => Ok(Operation::Add),
-            "remove" => Ok(Operation::Remove),
-            "complete" => Ok(Operation::Complete),
-            "list" => Ok(Operation::List),
-            _ => Err("Could not parse filter"),
+    fn from_str(sort: &str) -> Result<Self, Self::Err> {
+        match sort {
+            "id" => Ok(Sort::Id),
+            "priority" => Ok(Sort::Priority),
+            "due" => Ok(Sort::Due),
+            _ => Err(ParseError::new("sort order", sort)),
         }
     }
 }
 
-impl fmt::Display for Operation {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{:?}", self)
+impl Sort {
+    /// Orders tasks for listing. Missing priorities and due dates sort last,
+    /// and the task ID breaks any remaining ties.
+    fn compare(&self, a: &Task, b: &Task) -> Ordering {
+        let priority = || option_last(a.priority, b.priority);
+        let due = || option_last(a.due, b.due);
+        match self {
+            Sort::Id => Ordering::Equal,
+            Sort::Priority => priority().then_with(due),
+            Sort::Due => due().then_with(priority),
+        }
+        .then(a.id.cmp(&b.id))
     }
 }
 
-#[derive(Debug)]
-enum Filter {
-    None,
-    Pending,
-    Completed,
+fn option_last<T: Ord>(a: Option<T>, b: Option<T>) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => a.cmp(&b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
 }
 
-impl FromStr for Filter {
-    type Err = ParseError;
-    fn from_str(filter: &str) -> Result<Self, Self::Err> {
-        match filter {
-            "none" => Ok(Filter::None),
-            "" => Ok(Filter::None),
-            "pending" => Ok(Filter::Pending),
-            "completed" => Ok(Filter::Completed),
-            _ => Err("Could not parse filter"),
+#[derive(StructOpt, Debug)]
+#[structopt(rename_all = "kebab-case", name = "Task-cli", about = "Task-cli usage")]
+struct Opt {
+    /// Path to the task file; defaults to $TODO_FILE, the `file` set in
+    /// config.toml, or todo.json in the user's data directory
+    #[structopt(short, long, global = true, parse(from_os_str))]
+    json: Option<PathBuf>,
+
+    /// Name of the list to use, stored as <name>.json in the lists directory
+    /// of the data directory, e.g. ~/.local/share/todo/lists; the task file
+    /// itself is the list named after it, usually `todo`
+    #[structopt(short, long, global = true, parse(try_from_str = list::parse))]
+    list: Option<String>,
+
+    /// When to color output: auto, always, never
+    #[structopt(long, global = true)]
+    color: Option<ColorMode>,
+
+    #[structopt(subcommand)]
+    command: Command,
+}
+
+#[derive(StructOpt, Debug)]
+#[structopt(rename_all = "kebab-case")]
+enum Command {
+    /// Add a new task
+    Add {
+        /// Description of the task; the remaining words are joined with
+        /// spaces, except words such as +work, which are taken as tags
+        #[structopt(required = true)]
+        description: Vec<String>,
+
+        /// Due date, e.g. 2026-11-03, tomorrow, next friday, in 3 days, eod
+        #[structopt(long, parse(try_from_str = due::parse))]
+        due: Option<DateTime<Utc>>,
+
+        /// Priority: high, medium, low (or h, m, l)
+        #[structopt(short, long)]
+        priority: Option<Priority>,
+
+        /// Tag to attach, e.g. +work; may be repeated
+        #[structopt(short, long, number_of_values = 1, parse(try_from_str = task::parse_tag))]
+        tag: Vec<String>,
+
+        /// Project; dotted names nest, e.g. infra.ci.flaky
+        #[structopt(long, parse(try_from_str = project::parse))]
+        project: Option<String>,
+
+        /// Repeat the task, e.g. daily, "every 2 weeks", "every monday",
+        /// "monthly on the 1st", "3 days after completion"
+        #[structopt(long)]
+        recur: Option<Recurrence>,
+
+        /// Make the task a subtask of this one
+        #[structopt(long)]
+        parent: Option<usize>,
+
+        /// Task that must be closed before this one can start; may be repeated
+        #[structopt(long, number_of_values = 1)]
+        depends_on: Vec<usize>,
+    },
+
+    /// Mark tasks as completed. A task with open subtasks is left alone
+    /// unless --force or --recursive is given
+    #[structopt(alias = "complete")]
+    Done {
+        /// IDs of the tasks, as shown by ls
+        #[structopt(required = true)]
+        ids: Vec<usize>,
+
+        /// Complete the tasks even if they have open subtasks
+        #[structopt(short, long)]
+        force: bool,
+
+        /// Complete the open subtasks of the tasks as well
+        #[structopt(short, long, conflicts_with = "force")]
+        recursive: bool,
+    },
+
+    /// Set the status of tasks: pending, in-progress, blocked, waiting,
+    /// completed or cancelled
+    Status {
+        /// New status
+        status: Status,
+
+        /// IDs of the tasks, as shown by ls
+        #[structopt(required = true)]
+        ids: Vec<usize>,
+    },
+
+    /// Mark tasks as cancelled
+    Cancel {
+        /// IDs of the tasks, as shown by ls
+        #[structopt(required = true)]
+        ids: Vec<usize>,
+    },
+
+    /// Return completed or cancelled tasks to pending
+    Reopen {
+        /// IDs of the tasks, as shown by ls
+        #[structopt(required = true)]
+        ids: Vec<usize>,
+    },
+
+    /// Remove tasks
+    #[structopt(alias = "remove")]
+    Rm {
+        /// IDs of the tasks, as shown by ls
+        #[structopt(required = true)]
+        ids: Vec<usize>,
+    },
+
+    /// List tasks
+    #[structopt(alias = "list")]
+    Ls {
+        /// Filter query, e.g. "status:pending and (tag:work or due.before:friday)".
+        /// Supports status:, tag: (+tag), project:, priority:, due.before:, due.after:,
+        /// description: and description.regex: terms combined with and, or, not and
+        /// parentheses; may be repeated and all filters must match. Defaults to the
+        /// `filter` set in config.toml
+        #[structopt(short, long, number_of_values = 1, allow_hyphen_values = true)]
+        filter: Vec<Filter>,
+
+        /// Listing order: id (default), priority, due
+        #[structopt(short, long)]
+        sort: Option<Sort>,
+
+        /// List archived tasks instead
+        #[structopt(long)]
+        archived: bool,
+    },
+
+    /// Change the priority of a task
+    Prioritize {
+        /// ID of the task, as shown by ls
+        id: usize,
+
+        /// Priority: high, medium, low (or h, m, l)
+        priority: Priority,
+    },
+
+    /// Attach tags to a task
+    Tag {
+        /// ID of the task, as shown by ls
+        id: usize,
+
+        /// Tags to attach, e.g. +work
+        #[structopt(required = true, parse(try_from_str = task::parse_tag))]
+        tags: Vec<String>,
+    },
+
+    /// Remove tags from a task
+    Untag {
+        /// ID of the task, as shown by ls
+        id: usize,
+
+        /// Tags to remove, e.g. +work
+        #[structopt(required = true, parse(try_from_str = task::parse_tag))]
+        tags: Vec<String>,
+    },
+
+    /// Change the description or other fields of a task in place
+    Modify {
+        /// ID of the task, as shown by ls
+        id: usize,
+
+        /// New description; the remaining words are joined with spaces
+        description: Vec<String>,
+
+        /// New due date, e.g. 2026-11-03, tomorrow, next friday, in 3 days, eod
+        #[structopt(long, parse(try_from_str = due::parse))]
+        due: Option<DateTime<Utc>>,
+
+        /// Clear the due date
+        #[structopt(long, conflicts_with = "due")]
+        no_due: bool,
+
+        /// New priority: high, medium, low (or h, m, l)
+        #[structopt(short, long)]
+        priority: Option<Priority>,
+
+        /// Clear the priority
+        #[structopt(long, conflicts_with = "priority")]
+        no_priority: bool,
+
+        /// Tag to attach, e.g. +work; may be repeated
+        #[structopt(short, long, number_of_values = 1, parse(try_from_str = task::parse_tag))]
+        tag: Vec<String>,
+
+        /// Tag to remove, e.g. +work; may be repeated
+        #[structopt(long, number_of_values = 1, parse(try_from_str = task::parse_tag))]
+        untag: Vec<String>,
+
+        /// New project; dotted names nest, e.g. infra.ci.flaky
+        #[structopt(long, parse(try_from_str = project::parse))]
+        project: Option<String>,
+
+        /// Clear the project
+        #[structopt(long, conflicts_with = "project")]
+        no_project: bool,
+
+        /// Repeat the task, or change how its series repeats
+        #[structopt(long)]
+        recur: Option<Recurrence>,
+
+        /// Stop the series the task belongs to
+        #[structopt(long, conflicts_with = "recur")]
+        no_recur: bool,
+
+        /// Make the task a subtask of this one
+        #[structopt(long)]
+        parent: Option<usize>,
+
+        /// Make the task a top-level task again
+        #[structopt(long, conflicts_with = "parent")]
+        no_parent: bool,
+
+        /// Task that must be closed before this one can start; may be repeated
+        #[structopt(long, number_of_values = 1)]
+        depends_on: Vec<usize>,
+
+        /// Task to no longer depend on; may be repeated
+        #[structopt(long, number_of_values = 1)]
+        drop_dependency: Vec<usize>,
+    },
+
+    /// Edit a task in $EDITOR
+    Edit {
+        /// ID of the task, as shown by ls
+        id: usize,
+    },
+
+    /// Append a timestamped annotation to a task
+    Annotate {
+        /// ID of the task, as shown by ls
+        id: usize,
+
+        /// Text of the annotation; the remaining words are joined with spaces
+        #[structopt(required = true)]
+        text: Vec<String>,
+    },
+
+    /// Edit the notes of a task in $EDITOR; saving them empty removes them
+    Notes {
+        /// ID of the task, as shown by ls
+        id: usize,
+    },
+
+    /// Show every detail of a task, with its history, annotations and notes
+    Show {
+        /// ID of the task, as shown by ls
+        id: usize,
+    },
+
+    /// Search descriptions, annotations and notes, best matches first.
+    /// Words match case-insensitively, as prefixes, or with a typo or two
+    Search {
+        /// Words to search for; a task must match all of them
+        #[structopt(required = true)]
+        query: Vec<String>,
+
+        /// Most matches to show
+        #[structopt(short = "n", long, default_value = "20")]
+        limit: usize,
+    },
+
+    /// Start tracking time on a task, stopping whichever task is active
+    Start {
+        /// ID of the task, as shown by ls
+        id: usize,
+    },
+
+    /// Stop tracking time on the active task
+    Stop,
+
+    /// Total the time tracked per task, tag and project
+    Report {
+        /// First day of the report, e.g. 2026-10-01, monday for the most
+        /// recent one, or last monday; defaults to the Monday of this week
+        #[structopt(long, parse(try_from_str = due::parse_past))]
+        from: Option<DateTime<Utc>>,
+
+        /// Last day of the report; defaults to today
+        #[structopt(long, parse(try_from_str = due::parse_past))]
+        to: Option<DateTime<Utc>>,
+
+        /// Grouping to show: task, tag or project; may be repeated. Defaults
+        /// to all three
+        #[structopt(long, number_of_values = 1)]
+        by: Vec<report::Group>,
+
+        /// Print CSV rows of group, name and hours instead of tables
+        #[structopt(long)]
+        csv: bool,
+    },
+
+    /// Show the project tree with task counts
+    Projects,
+
+    /// Reverse the most recent changes
+    Undo {
+        /// Number of changes to reverse
+        #[structopt(default_value = "1")]
+        count: usize,
+    },
+
+    /// Reapply the most recently undone changes
+    Redo {
+        /// Number of changes to reapply
+        #[structopt(default_value = "1")]
+        count: usize,
+    },
+
+    /// Move tasks to another list, where they are given new IDs
+    #[structopt(alias = "mv")]
+    Move {
+        /// IDs of the tasks, as shown by ls
+        #[structopt(required = true)]
+        ids: Vec<usize>,
+
+        /// Name of the list to move them to
+        #[structopt(long, parse(try_from_str = list::parse))]
+        to: String,
+    },
+
+    /// Show every list with its task counts
+    Lists,
+
+    /// Move completed and cancelled tasks closed some days ago to the
+    /// archive kept beside the task file. Run it from cron or a timer to
+    /// archive on a schedule, or set `archive-after` in config.toml
+    Archive {
+        /// Archive tasks closed more than this many days ago; defaults to
+        /// `archive-after` in config.toml, or 30
+        #[structopt(long)]
+        days: Option<u32>,
+    },
+
+    /// Move archived tasks back into the list, keeping their IDs
+    Restore {
+        /// IDs of the tasks, as shown by ls --archived
+        #[structopt(required = true)]
+        ids: Vec<usize>,
+    },
+
+    /// Upgrade the task file to the current schema version, or copy the tasks
+    /// into a new file, converting between storage backends; a .db, .sqlite or
+    /// .sqlite3 file is a SQLite database, anything else JSON
+    Migrate {
+        /// File to copy the tasks to; without one the task file is upgraded in place
+        #[structopt(parse(from_os_str))]
+        to: Option<PathBuf>,
+
+        /// Report what would change without writing anything
+        #[structopt(long)]
+        dry_run: bool,
+    },
+
+    /// Show which task file is used and why
+    Where,
+
+    /// Read or change settings in config.toml
+    Config {
+        #[structopt(subcommand)]
+        action: ConfigAction,
+    },
+}
+
+#[derive(StructOpt, Debug)]
+#[structopt(rename_all = "kebab-case")]
+enum ConfigAction {
+    /// Print a setting, e.g. `sort` or `theme.overdue`
+    Get { key: String },
+
+    /// Change a setting, e.g. `set sort due` or `set glyphs.completed x`
+    Set { key: String, value: String },
+
+    /// Remove a setting, restoring its default
+    Unset { key: String },
+}
+
+impl Command {
+    /// Whether the command only reads the task file, so it can share the
+    /// file lock with other readers.
+    fn is_read_only(&self) -> bool {
+        matches!(
+            self,
+            Command::Ls { .. }
+                | Command::Projects
+                | Command::Show { .. }
+                | Command::Report { .. }
+                | Command::Search { .. }
+        )
+    }
+}
+
+/// Separates the words of a description given to `add` from the `+tag`
+/// words among them.
+fn split_tags(words: Vec<String>) -> Result<(String, Vec<String>), Error> {
+    let (tags, words): (Vec<String>, Vec<String>) = words
+        .into_iter()
+        .partition(|word| word.len() > 1 && word.starts_with('+'));
+    if words.is_empty() {
+        return Err(Error::Validation(
+            "The task needs a description besides its tags".to_string(),
+        ));
+    }
+    let tags = tags
+        .iter()
+        .map(|tag| task::parse_tag(tag))
+        .collect::<Result<_, _>>()
+        .map_err(|e| Error::Validation(e.to_string()))?;
+    Ok((words.join(" "), tags))
+}
+
+/// Turns a `--field`/`--no-field` flag pair into a change for `Changes`.
+fn change<T>(value: Option<T>, clear: bool) -> Option<Option<T>> {
+    if clear {
+        Some(None)
+    } else {
+        value.map(Some)
+    }
+}
+
+/// Tasks keyed by their persistent ID, along with the next ID to hand out.
+/// IDs are never reused, even after the task holding one is removed.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct TaskList {
+    next_id: usize,
+    #[serde(with = "task_map")]
+    tasks: BTreeMap<usize, Task>,
+}
+
+impl Default for TaskList {
+    fn default() -> Self {
+        TaskList {
+            next_id: 1,
+            tasks: BTreeMap::new(),
         }
     }
 }
 
-impl fmt::Display for Filter {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{:?}", self)
+mod task_map {
+    use crate::task::Task;
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::collections::BTreeMap;
+
+    pub fn serialize<S: Serializer>(
+        tasks: &BTreeMap<usize, Task>,
+        s: S,
+    ) -> Result<S::Ok, S::Error> {
+        s.collect_seq(tasks.values())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<BTreeMap<usize, Task>, D::Error> {
+        let tasks = Vec::<Task>::deserialize(d)?;
+        Ok(tasks.into_iter().map(|t| (t.id, t)).collect())
     }
 }
 
-#[derive(StructOpt, Debug)]
-#[structopt(rename_all = "kebab-case", name = "Task-cli", about = "Task-cli usage")]
-struct Opt {
-    /// Operation (-o, --operation) add, remove, complete, list
-    #[structopt(short, long, rename_all = "lower")]
-    operation: Operation,
-
-    /// ID (int) of task for completion or removal
-    #[structopt(
-        short,
-        long,
-        required_if("operation", "remove"),
-        required_if("operation", "complete")
-    )]
-    id: Option<usize>,
-
-    /// Description (-d --description) of Task item
-    #[structopt(short, long, required_if("operation", "add"))]
-    description: Option<String>,
-
-    /// Listing filter for tasks: none (default), pending, completed
-    #[structopt(
-        short,
-        long,
-        required_if("operation", "list"),
-        default_value = "none",
-        rename_all = "lower"
-    )]
-    filter: Filter,
-
-    /// Path to Task file otherwise defaults to Task.json in the binary's directory
-    #[structopt(short, long, parse(from_os_str))]
-    json: Option<PathBuf>,
+/// Reads the task list at `path` with the backend its extension selects.
+fn deserialize_tasks(path: &Path) -> Result<TaskList, Error> {
+    storage::open(path)?.load()
+}
+
+/// Stores `tasks` at `path`, where `before` is the list as it was loaded.
+fn serialize_tasks(path: &Path, before: &TaskList, tasks: &TaskList) -> Result<(), Error> {
+    storage::open(path)?.save(before, tasks)
+}
+
+fn find_task(id: usize, tasks: &mut TaskList) -> Result<&mut Task, Error> {
+    tasks.tasks.get_mut(&id).ok_or(Error::NotFound(id))
+}
+
+fn add_task(mut task: Task, tasks: &mut TaskList) {
+    task.id = tasks.next_id;
+    tasks.next_id += 1;
+    tasks.tasks.insert(task.id, task);
+}
+
+fn remove_tasks(ids: &[usize], tasks: &mut TaskList) -> Result<(), Error> {
+    for id in ids {
+        links::detach(*id, &mut tasks.tasks);
+        tasks.tasks.remove(id).ok_or(Error::NotFound(*id))?;
+    }
+    Ok(())
+}
+
+/// Completes tasks. One with open subtasks is refused, unless `force`
+/// completes it anyway or `recursive` completes the subtasks first.
+fn complete_tasks(
+    ids: &[usize],
+    tasks: &mut TaskList,
+    force: bool,
+    recursive: bool,
+) -> Result<(), Error> {
+    let mut completing = Vec::new();
+    for id in ids {
+        find_task(*id, tasks)?;
+        let open = links::open_subtasks(*id, &tasks.tasks);
+        if !open.is_empty() && !force && !recursive {
+            return Err(Error::Validation(format!(
+                "Task {} has open subtasks: {}; complete them too with --recursive, \
+                 or complete it alone with --force",
+                id,
+                join_ids(&open)
+            )));
+        }
+        if !open.is_empty() && force {
+            println!("Task {} still has open subtasks: {}", id, join_ids(&open));
+        }
+        let subtasks = if recursive { open } else { Vec::new() };
+        for task in subtasks.into_iter().chain(Some(*id)) {
+            if !completing.contains(&task) {
+                completing.push(task);
+            }
+        }
+    }
+    transition_tasks(&completing, tasks, Task::complete)
+}
+
+fn join_ids(ids: &[usize]) -> String {
+    ids.iter()
+        .map(|id| id.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Applies a status change to each task, stopping at the first one the
+/// change is not valid for. Nothing is saved unless every change succeeds.
+fn transition_tasks<F>(ids: &[usize], tasks: &mut TaskList, change: F) -> Result<(), Error>
+where
+    F: Fn(&mut Task) -> Result<(), TransitionError>,
+{
+    for id in ids {
+        change(find_task(*id, tasks)?)
+            .map_err(|e| Error::Validation(format!("Task {} was not changed: {}", id, e)))?;
+    }
+    for id in ids {
+        renew_series(*id, tasks);
+    }
+    Ok(())
+}
+
+/// Starts a recurring series: a template holding the rule, and a first
+/// instance of `task` linked to it.
+fn add_series(task: Task, recur: Recurrence, tasks: &mut TaskList) {
+    add_task(task.template(recur), tasks);
+    let template = tasks.next_id - 1;
+    let instance = Task {
+        due: task.due.or_else(|| recur.first(Utc::now())),
+        template: Some(template),
+        ..task
+    };
+    add_task(instance, tasks);
+    println!(
+        "Added task {} ({}), the first of series {}",
+        tasks.next_id - 1,
+        recur,
+        template
+    );
+}
+
+/// The template of the series task `id` belongs to, if it is still running.
+fn running_template(id: usize, tasks: &TaskList) -> Option<usize> {
+    let task = tasks.tasks.get(&id)?;
+    let template = match task.status {
+        Status::Recurring => id,
+        _ => task.template?,
+    };
+    tasks
+        .tasks
+        .get(&template)
+        .filter(|t| t.status == Status::Recurring)
+        .map(|t| t.id)
+}
+
+/// Adds the next instance of a series once task `id`, one of its instances,
+/// is closed. Nothing is added while another instance is still open.
+fn renew_series(id: usize, tasks: &mut TaskList) {
+    let task = &tasks.tasks[&id];
+    if task.status.is_open() || task.template.is_none() {
+        return;
+    }
+    let template = match running_template(id, tasks) {
+        Some(template) => &tasks.tasks[&template],
+        None => return,
+    };
+    let pending = tasks
+        .tasks
+        .values()
+        .any(|t| t.template == Some(template.id) && t.status.is_open());
+    if pending {
+        return;
+    }
+    let recur = template.recur.expect("recurring templates have a rule");
+    let closed = task
+        .history
+        .last()
+        .map_or_else(Utc::now, |change| change.at);
+    let instance = template.instance(recur.next(task.due, closed));
+    add_task(instance, tasks);
+    println!(
+        "Task {} recurs {}; added task {}",
+        id,
+        recur,
+        tasks.next_id - 1
+    );
+}
+
+/// Applies `--recur` or `--no-recur` to task `id`. Changing the rule of an
+/// instance changes its series; giving a rule to an ordinary open task
+/// starts a series with it as the first instance.
+fn set_recurrence(id: usize, recur: Option<Recurrence>, tasks: &mut TaskList) -> Result<(), Error> {
+    let running = running_template(id, tasks);
+    match (recur, running) {
+        (Some(recur), Some(template)) => {
+            let template = find_task(template, tasks)?;
+            template.recur = Some(recur);
+            template.modified = Some(Utc::now());
+        }
+        (Some(recur), None) if find_task(id, tasks)?.recur.is_some() => {
+            find_task(id, tasks)?.restart_recurring(recur);
+            let open = tasks
+                .tasks
+                .values()
+                .any(|t| t.template == Some(id) && t.status.is_open());
+            if !open {
+                let instance = tasks.tasks[&id].instance(recur.first(Utc::now()));
+                add_task(instance, tasks);
+            }
+        }
+        (Some(recur), None) => {
+            let task = find_task(id, tasks)?;
+            if !task.status.is_open() {
+                return Err(Error::Validation(format!(
+                    "Task {} is {}; reopen it before making it recur",
+                    id, task.status
+                )));
+            }
+            let template = task.template(recur);
+            add_task(template, tasks);
+            let template = tasks.next_id - 1;
+            let task = find_task(id, tasks)?;
+            task.template = Some(template);
+            task.modified = Some(Utc::now());
+        }
+        (None, Some(template)) => find_task(template, tasks)?.stop_recurring(),
+        (None, None) => {
+            find_task(id, tasks)?;
+            return Err(Error::Validation(format!(
+                "Task {} is not part of a running series",
+                id
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn prioritize_task(id: usize, priority: Priority, tasks: &mut TaskList) -> Result<(), Error> {
+    find_task(id, tasks)?.prioritize(priority);
+    Ok(())
+}
+
+fn tag_task(id: usize, tags: &[String], tasks: &mut TaskList) -> Result<(), Error> {
+    find_task(id, tasks)?.tag(tags);
+    Ok(())
+}
+
+fn untag_task(id: usize, tags: &[String], tasks: &mut TaskList) -> Result<(), Error> {
+    find_task(id, tasks)?.untag(tags);
+    Ok(())
 }
 
-fn deserialize_tasks(path: PathBuf) -> Result<BTreeMap<usize, Task>, Box<dyn Error>> {
-    let file = OpenOptions::new()
-        .read(true)
-        .write(true)
-        .create(true)
-        .open(path)?;
-    match serde_json::from_reader(file) {
-        Ok(tasks) => Ok(vec_to_map(tasks)),
-        Err(e) if e.is_eof() => Ok(BTreeMap::new()),
-        Err(e) => panic!("An error occurred: {}", e),
+/// Changes a task. Changes to a recurring template apply to every future
+/// instance and to any still open; its due date belongs to those instances.
+fn modify_task(id: usize, changes: Changes, tasks: &mut TaskList) -> Result<(), Error> {
+    let task = find_task(id, tasks)?;
+    if changes.is_empty() {
+        println!("Nothing to change for task {}", id);
+        return Ok(());
+    }
+    if task.status != Status::Recurring {
+        task.modify(changes);
+        return links::check(id, &tasks.tasks);
+    }
+    task.modify(Changes {
+        due: None,
+        ..changes.clone()
+    });
+    let mut modified = vec![id];
+    for instance in tasks.tasks.values_mut() {
+        if instance.template == Some(id) && instance.status.is_open() {
+            instance.modify(changes.clone());
+            modified.push(instance.id);
+        }
+    }
+    for id in modified {
+        links::check(id, &tasks.tasks)?;
     }
+    Ok(())
 }
 
-fn vec_to_map(tasks: Vec<Task>) -> BTreeMap<usize, Task> {
-    let mut map = BTreeMap::new();
-    let mut i = 1;
-    for t in tasks {
-        map.insert(i, t);
-        i += 1;
+fn edit_task(id: usize, tasks: &mut TaskList) -> Result<(), Error> {
+    let changes = editor::edit_task(find_task(id, tasks)?)?;
+    modify_task(id, changes, tasks)
+}
+
+/// Starts tracking task `id`, first stopping any other active task.
+fn start_task(id: usize, tasks: &mut TaskList) -> Result<(), Error> {
+    let task = find_task(id, tasks)?;
+    if task.is_active() {
+        return Err(Error::Validation(format!("Task {} is already active", id)));
+    }
+    if !task.status.is_open() {
+        return Err(Error::Validation(format!(
+            "Task {} is {}; reopen it before starting it",
+            id, task.status
+        )));
+    }
+    for other in tasks.tasks.values_mut().filter(|t| t.is_active()) {
+        other.stop();
+        println!("Stopped task {}", other.id);
     }
-    return map;
+    find_task(id, tasks)?
+        .start()
+        .map_err(|e| Error::Validation(format!("Task {} was not started: {}", id, e)))?;
+    println!("Started task {}", id);
+    Ok(())
 }
 
-fn serialize_tasks(path: PathBuf, tasks: Vec<Task>) -> Result<(), Box<dyn Error>> {
-    let file = File::create(path)?;
-    match serde_json::to_writer(file, &tasks) {
-        Ok(()) => Ok(()),
-        Err(e) => panic!("An error occurred: {}", e),
+fn stop_task(tasks: &mut TaskList) -> Result<(), Error> {
+    let task = tasks
+        .tasks
+        .values_mut()
+        .find(|t| t.is_active())
+        .ok_or_else(|| Error::Validation("No task is active".to_string()))?;
+    task.stop();
+    let spent = task.intervals.last().and_then(|i| Some(i.end? - i.start));
+    println!(
+        "Stopped task {} after {}",
+        task.id,
+        report::format_duration(spent.unwrap_or_else(chrono::Duration::zero))
+    );
+    Ok(())
+}
+
+/// Archives the tasks closed more than `days` days ago, returning how many
+/// there were.
+fn archive_tasks(
+    days: u32,
+    tasks: &mut TaskList,
+    path: &Path,
+    command: &str,
+) -> Result<usize, Error> {
+    let ids = archive::closed_before(tasks, days, Utc::now());
+    if ids.is_empty() {
+        return Ok(0);
     }
+    archive::archive(&ids, tasks, path, command)?;
+    println!(
+        "Archived {} task{} closed more than {} days ago",
+        ids.len(),
+        if ids.len() == 1 { "" } else { "s" },
+        days
+    );
+    Ok(ids.len())
 }
 
-fn add_task(description: String, mut tasks: BTreeMap<usize, Task>, path: PathBuf) -> () {
-    let key = tasks.len() + 1;
-    tasks.insert(key, Task::new(description));
-    serialize_tasks(path, tasks.values().cloned().collect()).expect("Failed to write to file");
+fn annotate_task(id: usize, text: String, tasks: &mut TaskList) -> Result<(), Error> {
+    find_task(id, tasks)?.annotate(text);
+    Ok(())
 }
 
-fn remove_task(id: usize, mut tasks: BTreeMap<usize, Task>, path: PathBuf) -> () {
-    tasks.remove(&id);
-    serialize_tasks(path, tasks.values().cloned().collect()).expect("Failed to write to file");
+fn edit_notes(id: usize, tasks: &mut TaskList) -> Result<(), Error> {
+    let task = find_task(id, tasks)?;
+    let notes = editor::edit_notes(task)?;
+    if notes == task.notes {
+        println!("Nothing to change for task {}", id);
+        return Ok(());
+    }
+    task.set_notes(notes);
+    Ok(())
 }
 
-fn complete_task(id: usize, mut tasks: BTreeMap<usize, Task>, path: PathBuf) -> () {
-    let mut task = tasks.get(&id).expect("Task not found").clone();
-    task.complete();
-    tasks.insert(id, task);
-    serialize_tasks(path, tasks.values().cloned().collect()).expect("Failed to write to file");
+/// Moves tasks from the list at `source` into the list at `target`, which
+/// numbers them after its own tasks. Links among the moved tasks follow them
+/// to their new IDs; links to tasks left behind are dropped. The target is
+/// saved first, so a failure part way through leaves a task in both lists
+/// rather than in neither. Returns when the target's half of the move was
+/// journaled, so that the source's half can be linked to it.
+fn move_tasks(
+    ids: &[usize],
+    tasks: &mut TaskList,
+    source: &Path,
+    target: &Path,
+    command: &str,
+) -> Result<DateTime<Utc>, Error> {
+    let mut target_tasks = deserialize_tasks(target)?;
+    let before = target_tasks.clone();
+    let mut new_ids = BTreeMap::new();
+    let mut moved = Vec::new();
+    for id in ids {
+        if new_ids.contains_key(id) {
+            continue;
+        }
+        moved.push(find_task(*id, tasks)?.clone());
+        new_ids.insert(*id, target_tasks.next_id + new_ids.len());
+    }
+    for task in &moved {
+        links::detach(task.id, &mut tasks.tasks);
+        tasks.tasks.remove(&task.id);
+    }
+    let name = list::name_of(target);
+    let new_id = |id: &usize| new_ids.get(id).copied();
+    for mut task in moved {
+        let id = task.id;
+        task.template = task.template.as_ref().and_then(new_id);
+        task.parent = task.parent.as_ref().and_then(new_id);
+        task.depends_on = task.depends_on.iter().filter_map(new_id).collect();
+        add_task(task, &mut target_tasks);
+        println!(
+            "Moved task {} to {} as {}",
+            id,
+            name,
+            target_tasks.next_id - 1
+        );
+    }
+    let journal_path = journal::path_for(target);
+    let mut journal = Journal::load(&journal_path)?;
+    journal.record(command.to_string(), &before, &target_tasks);
+    let at = journal.link_latest(source, None);
+    serialize_tasks(target, &before, &target_tasks)?;
+    journal.save(&journal_path)?;
+    Ok(at)
 }
 
-fn print_tasks(tasks: BTreeMap<usize, Task>, filter: Filter) -> () {
-    let header = format!("Task (filter: {})", filter.to_string().to_lowercase());
+/// Reports the schema upgrades the task file at `from` needs, then applies
+/// them in place or, given `to`, copies the upgraded tasks and their journal
+/// there. `to` must not hold any tasks yet; the original file is left as it
+/// was.
+fn migrate(from: &Path, to: Option<&Path>, dry_run: bool) -> Result<(), Error> {
+    let (tasks, upgrade) = storage::open(from)?.load_upgraded()?;
+    for note in &upgrade.notes {
+        println!("{}", note);
+    }
+    let versions = format!("schema version {} to {}", upgrade.from, schema::VERSION);
+    let to = match to {
+        Some(to) => to,
+        None if upgrade.from == schema::VERSION => {
+            println!(
+                "{} is already at schema version {}",
+                from.display(),
+                schema::VERSION
+            );
+            return Ok(());
+        }
+        None if dry_run => {
+            println!("Would upgrade {} from {}", from.display(), versions);
+            return Ok(());
+        }
+        None => {
+            serialize_tasks(from, &TaskList::default(), &tasks)?;
+            println!("Upgraded {} from {}", from.display(), versions);
+            return Ok(());
+        }
+    };
+    if to.exists() && !deserialize_tasks(to)?.tasks.is_empty() {
+        return Err(Error::Validation(format!(
+            "{} already has tasks; migrate into a new file",
+            to.display()
+        )));
+    }
+    if dry_run {
+        println!(
+            "Would migrate {} tasks to {}",
+            tasks.tasks.len(),
+            to.display()
+        );
+        return Ok(());
+    }
+    serialize_tasks(to, &TaskList::default(), &tasks)?;
+    let (from_journal, to_journal) = (journal::path_for(from), journal::path_for(to));
+    if from_journal != to_journal {
+        Journal::load(&from_journal)?.save(&to_journal)?;
+    }
+    println!(
+        "Migrated {} tasks to {}; run `todo config set file {}` to use it by default",
+        tasks.tasks.len(),
+        to.display(),
+        to.display()
+    );
+    Ok(())
+}
+
+/// Undoes, or redoes if `redo`, up to `count` entries in the journal of the
+/// list at `path`, and reports each. An entry for one half of a move is only
+/// applied along with its other half, in the journal of the other list,
+/// which is saved here; `locked` are the other lists this process holds.
+fn replay(
+    tasks: &mut TaskList,
+    journal: &mut Journal,
+    path: &Path,
+    redo: bool,
+    count: usize,
+    locked: &[PathBuf],
+) -> Result<(), Error> {
+    let verb = if redo { "redo" } else { "undo" };
+    let step = |journal: &mut Journal, tasks: &mut TaskList| {
+        if redo {
+            journal.redo(tasks, 1)
+        } else {
+            journal.undo(tasks, 1)
+        }
+    };
+    let here = journal::absolute(path);
+    let mut others: Vec<(PathBuf, TaskList, TaskList, Journal)> = Vec::new();
+    let mut applied = Vec::new();
+    for _ in 0..count {
+        let entry = match journal.next(redo) {
+            Some(entry) => entry.clone(),
+            None => break,
+        };
+        if let Some(other) = &entry.linked {
+            if !locked.contains(other) {
+                return Err(Error::Validation(format!(
+                    "The journal changed while waiting for it; run {} again",
+                    verb
+                )));
+            }
+            let index = match others.iter().position(|(p, ..)| p == other) {
+                Some(index) => index,
+                None => {
+                    let other_tasks = deserialize_tasks(other)?;
+                    let other_journal = Journal::load(&journal::path_for(other))?;
+                    others.push((
+                        other.clone(),
+                        other_tasks.clone(),
+                        other_tasks,
+                        other_journal,
+                    ));
+                    others.len() - 1
+                }
+            };
+            let (_, _, other_tasks, other_journal) = &mut others[index];
+            let paired = other_journal
+                .next(redo)
+                .is_some_and(|half| half.at == entry.at && half.linked.as_ref() == Some(&here));
+            if !paired {
+                return Err(Error::Validation(format!(
+                    "`{}` also changed {}; {} the later changes there first",
+                    entry.command,
+                    other.display(),
+                    verb
+                )));
+            }
+            step(other_journal, other_tasks)?;
+        }
+        applied.extend(step(journal, tasks)?);
+    }
+    for (other, before, after, other_journal) in &others {
+        serialize_tasks(other, before, after)?;
+        other_journal.save(&journal::path_for(other))?;
+    }
+    if applied.is_empty() {
+        println!("Nothing to {}", verb);
+    }
+    for entry in applied {
+        println!("{}: {}", verb, entry.command);
+    }
+    Ok(())
+}
+
+fn print_tasks(tasks: &TaskList, filters: Vec<Filter>, sort: Sort, config: &Config, color: bool) {
+    let filter = match filters.len() {
+        0 => "none".to_string(),
+        _ => filters
+            .iter()
+            .map(|f| f.to_string())
+            .collect::<Vec<_>>()
+            .join(" "),
+    };
+    let header = format!("Task (filter: {})", filter);
     let line_break = (0..header.len()).map(|_| "─").collect::<String>();
     println!("{}", header);
     println!("{}", line_break);
 
-    tasks
-        .iter()
-        .filter(|(_id, task)| match filter {
-            Filter::Completed => task.status == task::COMPLETED,
-            Filter::Pending => task.status == task::PENDING,
-            Filter::None => true,
-        })
-        .for_each(|(id, task)| {
-            println!("{} - {} [{}]", id, task.description, task.status);
-        });
+    let theme = &config.theme;
+    let paint = |c: color::Color, text: &str| {
+        if color {
+            c.paint(text)
+        } else {
+            text.to_string()
+        }
+    };
+    let now = Utc::now();
+    let mut listed: Vec<&Task> = tasks
+        .tasks
+        .values()
+        .filter(|task| filters.iter().all(|f| f.matches(task, &tasks.tasks, now)))
+        .collect();
+    listed.sort_by(|a, b| sort.compare(a, b));
+    for (depth, task) in links::tree(&listed) {
+        let mut line = format!("{}{} - ", "  ".repeat(depth), task.id);
+        if let Some(priority) = task.priority {
+            line.push_str(&paint(theme.priority, &format!("({})", priority)));
+            line.push(' ');
+        }
+        line.push_str(&task.description);
+        for tag in &task.tags {
+            line.push(' ');
+            line.push_str(&paint(theme.tag, &format!("+{}", tag)));
+        }
+        let waiting = task.status.is_open() && links::has_open_dependencies(task, &tasks.tasks);
+        let shown = if waiting {
+            Status::Blocked
+        } else {
+            task.status
+        };
+        let glyph = format!("[{}]", config.glyphs.get(shown));
+        let glyph = match task.status {
+            Status::Completed => paint(theme.completed, &glyph),
+            Status::Cancelled => paint(theme.cancelled, &glyph),
+            _ => glyph,
+        };
+        line.push(' ');
+        line.push_str(&glyph);
+        if let Some(recur) = task.recur {
+            line.push_str(&format!(" ({})", recur));
+        }
+        if let Some(interval) = task.intervals.last().filter(|i| i.end.is_none()) {
+            let spent = report::format_duration(now - interval.start);
+            line.push(' ');
+            line.push_str(&paint(color::Color::Bold, &format!("(active, {})", spent)));
+        }
+        if waiting {
+            let open: Vec<usize> = task
+                .depends_on
+                .iter()
+                .copied()
+                .filter(|id| tasks.tasks.get(id).is_some_and(|t| t.status.is_open()))
+                .collect();
+            line.push_str(&format!(" (after {})", join_ids(&open)));
+        }
+        if let Some(due) = task.due {
+            let when = match &config.date_format {
+                Some(format) => format.format(due),
+                None => due::humanize(due, now),
+            };
+            let due_color = if task.is_overdue(now) {
+                theme.overdue
+            } else {
+                theme.due
+            };
+            line.push(' ');
+            line.push_str(&paint(due_color, &format!("(due {})", when)));
+        }
+        println!("{}", line);
+    }
 }
 
 fn main() {
-    let opt = Opt::from_args();
-    let operation = opt.operation;
-    let default_path = dirs::document_dir().unwrap().with_file_name("todo.json");
-    let path = opt.json.unwrap_or_else(|| default_path);
-    let tasks = deserialize_tasks(path.clone()).expect("Error deserializing tasks");
-    match operation {
-        Operation::Add => add_task(opt.description.expect("Description missing."), tasks, path),
-        Operation::Remove => remove_task(opt.id.expect("Task id missing"), tasks, path),
-        Operation::Complete => complete_task(opt.id.expect("Task id missing"), tasks, path),
-        Operation::List => print_tasks(tasks, opt.filter),
+    if let Err(e) = run() {
+        match e {
+            Error::Usage(_) => eprintln!("{}", e),
+            _ => eprintln!("error: {}", e),
+        }
+        std::process::exit(e.exit_code());
+    }
+}
+
+fn run() -> Result<(), Error> {
+    let opt = Opt::from_iter_safe(std::env::args_os()).map_err(Error::usage)?;
+    // Handled before the config is read, so a broken setting can be fixed.
+    if let Command::Config { action } = &opt.command {
+        return match action {
+            ConfigAction::Get { key } => match config::get(key)? {
+                Some(value) => {
+                    println!("{}", value);
+                    Ok(())
+                }
+                None => Err(Error::Validation(format!("`{}` is not set", key))),
+            },
+            ConfigAction::Set { key, value } => config::set(key, Some(value)),
+            ConfigAction::Unset { key } => config::set(key, None),
+        };
+    }
+    let mut config = Config::load()?;
+    let (main_path, source) = config::task_file(opt.json, &config)?;
+    let path = match &opt.list {
+        Some(name) => list::path_for(&main_path, name)?,
+        None => main_path.clone(),
+    };
+    match &opt.command {
+        Command::Where => {
+            println!("{} ({})", path.display(), source);
+            return Ok(());
+        }
+        Command::Lists => return list::print_lists(&main_path, &path),
+        _ => {}
+    }
+    let target = match &opt.command {
+        Command::Move { to, .. } if list::path_for(&main_path, to)? == path => {
+            return Err(Error::Validation(format!(
+                "The tasks are already in {}",
+                to
+            )));
+        }
+        Command::Move { to, .. } => Some(list::path_for(&main_path, to)?),
+        Command::Migrate { to: Some(to), .. } if *to == path => {
+            return Err(Error::Validation(format!(
+                "The tasks are already in {}",
+                to.display()
+            )));
+        }
+        Command::Migrate { to, .. } => to.clone(),
+        _ => None,
+    };
+    // The default data directory and the lists directory are made on first
+    // use; any other directory named for a task file must already exist.
+    let lists_dir = list::dir().ok();
+    for file in Some(&path).into_iter().chain(&target) {
+        let dir = file.parent();
+        let made_here = if *file == main_path {
+            source == config::Source::Default
+        } else {
+            dir == lists_dir.as_deref()
+        };
+        if let Some(dir) = dir.filter(|_| made_here) {
+            fs::create_dir_all(dir).map_err(|e| Error::io("create", dir, e))?;
+        }
+    }
+    let archive_path = archive::path_for(&path);
+    let uses_archive = match &opt.command {
+        Command::Ls { archived, .. } => *archived,
+        Command::Archive { .. }
+        | Command::Restore { .. }
+        | Command::Search { .. }
+        | Command::Report { .. }
+        | Command::Show { .. } => true,
+        command => config.archive_after.is_some() && !command.is_read_only(),
+    };
+    // Not after a restore, which would archive the restored tasks again.
+    let archive_after = config
+        .archive_after
+        .filter(|_| !matches!(opt.command, Command::Restore { .. }));
+    let archive_lock = Some(&archive_path).filter(|_| uses_archive);
+    // Undoing or redoing half of a move changes the other list as well.
+    let linked = match &opt.command {
+        Command::Undo { count } => Journal::load(&journal::path_for(&path))?.linked(false, *count),
+        Command::Redo { count } => Journal::load(&journal::path_for(&path))?.linked(true, *count),
+        _ => Vec::new(),
+    };
+    // Held until run returns, so no other process can write between this
+    // process loading the tasks and saving its changes. Files are locked in
+    // path order so two moves in opposite directions cannot deadlock, and
+    // files sharing a lock, such as todo.json and todo.db, are locked once.
+    let mut locked: Vec<&PathBuf> = Some(&path)
+        .into_iter()
+        .chain(&target)
+        .chain(archive_lock)
+        .chain(&linked)
+        .collect();
+    locked.sort_by_key(|p| atomic::lock_path(p));
+    locked.dedup_by_key(|p| atomic::lock_path(p));
+    let _locks = locked
+        .into_iter()
+        .map(|p| {
+            atomic::lock(p, !opt.command.is_read_only())
+                .map_err(|e| Error::io("lock", &atomic::lock_path(p), e))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let invocation = std::env::args().skip(1).collect::<Vec<_>>().join(" ");
+    let command = match opt.command {
+        Command::Ls {
+            mut filter,
+            sort,
+            archived,
+        } => {
+            if filter.is_empty() {
+                filter.extend(config.filter.take());
+            }
+            let sort = sort.or(config.sort.take()).unwrap_or(Sort::Id);
+            let color = opt.color.or(config.color).unwrap_or(ColorMode::Auto);
+            let tasks = if archived {
+                let mut archived = archive::load(&archive_path)?;
+                let listed = deserialize_tasks(&path)?;
+                archived
+                    .tasks
+                    .retain(|id, _| !listed.tasks.contains_key(id));
+                archived
+            } else {
+                storage::open(&path)?.load_matching(&filter, Utc::now())?
+            };
+            print_tasks(&tasks, filter, sort, &config, color.enabled());
+            return Ok(());
+        }
+        Command::Migrate { to, dry_run } => return migrate(&path, to.as_deref(), dry_run),
+        command => command,
+    };
+    let mut tasks = deserialize_tasks(&path)?;
+    let journal_path = journal::path_for(&path);
+    let mut journal = Journal::load(&journal_path)?;
+    let before = tasks.clone();
+    let mut moved_at = None;
+    match command {
+        Command::Projects => {
+            project::print_tree(&tasks.tasks);
+            return Ok(());
+        }
+        Command::Report { from, to, by, csv } => {
+            let (from, to) = report::day_range(from, to)?;
+            let archived = archive::load(&archive_path)?;
+            let listed: Vec<&Task> = archive::only_archived(&archived, &tasks)
+                .chain(tasks.tasks.values())
+                .collect();
+            report::print(&listed, from, to, &by, csv);
+            return Ok(());
+        }
+        Command::Search { query, limit } => {
+            let query = query.join(" ");
+            let archived = archive::load(&archive_path)?;
+            let index = search::Index::build(
+                archive::only_archived(&archived, &tasks).chain(tasks.tasks.values()),
+            );
+            let hits = index.search(&query);
+            let color = opt.color.or(config.color).unwrap_or(ColorMode::Auto);
+            let is_archived = |id: usize| !tasks.tasks.contains_key(&id);
+            search::print(&query, &hits, limit, &config, color.enabled(), is_archived);
+            return Ok(());
+        }
+        Command::Show { id } => {
+            let color = opt.color.or(config.color).unwrap_or(ColorMode::Auto);
+            if let Some(task) = tasks.tasks.get(&id) {
+                show::print(task, &tasks, false, &config, color.enabled());
+                return Ok(());
+            }
+            let archived = archive::load(&archive_path)?;
+            let task = archived.tasks.get(&id).ok_or(Error::NotFound(id))?;
+            show::print(task, &archived, true, &config, color.enabled());
+            return Ok(());
+        }
+        Command::Undo { count } => {
+            replay(&mut tasks, &mut journal, &path, false, count, &linked)?;
+            serialize_tasks(&path, &before, &tasks)?;
+            return journal.save(&journal_path);
+        }
+        Command::Redo { count } => {
+            replay(&mut tasks, &mut journal, &path, true, count, &linked)?;
+            serialize_tasks(&path, &before, &tasks)?;
+            return journal.save(&journal_path);
+        }
+        Command::Add {
+            description,
+            due,
+            priority,
+            tag,
+            project,
+            recur,
+            parent,
+            depends_on,
+        } => {
+            let (description, tags) = split_tags(description)?;
+            let task = Task {
+                due,
+                priority,
+                tags: tag.into_iter().chain(tags).collect(),
+                project,
+                parent,
+                depends_on: depends_on.into_iter().collect(),
+                ..Task::new(description)
+            };
+            match recur {
+                Some(recur) => add_series(task, recur, &mut tasks),
+                None => add_task(task, &mut tasks),
+            }
+            links::check(tasks.next_id - 1, &tasks.tasks)?
+        }
+        Command::Done {
+            ids,
+            force,
+            recursive,
+        } => complete_tasks(&ids, &mut tasks, force, recursive)?,
+        Command::Status { status, ids } => {
+            transition_tasks(&ids, &mut tasks, |task| task.set_status(status))?
+        }
+        Command::Cancel { ids } => {
+            transition_tasks(&ids, &mut tasks, |task| task.set_status(Status::Cancelled))?
+        }
+        Command::Reopen { ids } => transition_tasks(&ids, &mut tasks, Task::reopen)?,
+        Command::Rm { ids } => remove_tasks(&ids, &mut tasks)?,
+        Command::Prioritize { id, priority } => prioritize_task(id, priority, &mut tasks)?,
+        Command::Tag { id, tags } => tag_task(id, &tags, &mut tasks)?,
+        Command::Untag { id, tags } => untag_task(id, &tags, &mut tasks)?,
+        Command::Modify {
+            id,
+            description,
+            due,
+            no_due,
+            priority,
+            no_priority,
+            tag,
+            untag,
+            project,
+            no_project,
+            recur,
+            no_recur,
+            parent,
+            no_parent,
+            depends_on,
+            drop_dependency,
+        } => {
+            let changes = Changes {
+                description: Some(description.join(" ")).filter(|d| !d.is_empty()),
+                due: change(due, no_due),
+                priority: change(priority, no_priority),
+                project: change(project, no_project),
+                add_tags: tag,
+                remove_tags: untag,
+                parent: change(parent, no_parent),
+                add_dependencies: depends_on,
+                remove_dependencies: drop_dependency,
+            };
+            let recurrence = recur.is_some() || no_recur;
+            if recurrence {
+                set_recurrence(id, recur, &mut tasks)?;
+            }
+            if !recurrence || !changes.is_empty() {
+                modify_task(id, changes, &mut tasks)?
+            }
+        }
+        Command::Edit { id } => edit_task(id, &mut tasks)?,
+        Command::Annotate { id, text } => annotate_task(id, text.join(" "), &mut tasks)?,
+        Command::Notes { id } => edit_notes(id, &mut tasks)?,
+        Command::Start { id } => start_task(id, &mut tasks)?,
+        Command::Archive { days } => {
+            let days = days
+                .or(config.archive_after)
+                .unwrap_or(archive::DEFAULT_DAYS);
+            if archive_tasks(days, &mut tasks, &archive_path, &invocation)? == 0 {
+                println!("No tasks were closed more than {} days ago", days);
+            }
+        }
+        Command::Restore { ids } => archive::restore(&ids, &mut tasks, &archive_path)?,
+        Command::Stop => stop_task(&mut tasks)?,
+        Command::Move { ids, .. } => {
+            let target = target.as_ref().expect("target is set for move");
+            moved_at = Some(move_tasks(&ids, &mut tasks, &path, target, &invocation)?);
+        }
+        Command::Ls { .. } | Command::Migrate { .. } => {
+            unreachable!("handled before the whole list is loaded")
+        }
+        Command::Where | Command::Lists | Command::Config { .. } => {
+            unreachable!("handled before the task file is opened")
+        }
+    }
+    if let Some(days) = archive_after {
+        archive_tasks(days, &mut tasks, &archive_path, &invocation)?;
+    }
+    if journal.record(invocation, &before, &tasks) {
+        if let (Some(at), Some(target)) = (moved_at, &target) {
+            journal.link_latest(target, Some(at));
+        }
+        serialize_tasks(&path, &before, &tasks)?;
+        journal.save(&journal_path)?;
     }
+    Ok(())
 }
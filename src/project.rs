@@ -0,0 +1,95 @@
+use crate::error::ParseError;
+use crate::status::Status;
+use crate::task::Task;
+use std::collections::BTreeMap;
+
+/// Parses a dotted project path such as `infra.ci.flaky`.
+pub fn parse(project: &str) -> Result<String, ParseError> {
+    let valid = project
+        .split('.')
+        .all(|part| !part.is_empty() && !part.contains(char::is_whitespace));
+    if !valid {
+        return Err(ParseError::new("project", project));
+    }
+    Ok(project.to_string())
+}
+
+/// Whether `project` is `parent` itself or one of its sub-projects.
+pub fn contains(parent: &str, project: &str) -> bool {
+    project == parent
+        || project
+            .strip_prefix(parent)
+            .is_some_and(|rest| rest.starts_with('.'))
+}
+
+#[derive(Default)]
+struct Counts {
+    pending: usize,
+    completed: usize,
+}
+
+impl Counts {
+    /// Open tasks of any state count as pending; cancelled tasks are not
+    /// counted.
+    fn count(&mut self, task: &Task) {
+        if task.status.is_open() {
+            self.pending += 1;
+        } else if task.status == Status::Completed {
+            self.completed += 1;
+        }
+    }
+}
+
+/// Prints every project as a tree. Each node counts the tasks filed directly
+/// under it and under all of its sub-projects.
+pub fn print_tree(tasks: &BTreeMap<usize, Task>) {
+    // Keyed by path segments rather than the dotted string, so that siblings
+    // like `infra-old` cannot sort between `infra` and `infra.ci`.
+    let mut nodes: BTreeMap<Vec<&str>, Counts> = BTreeMap::new();
+    let mut unfiled = Counts::default();
+    for task in tasks.values() {
+        match &task.project {
+            Some(project) => {
+                let parts: Vec<&str> = project.split('.').collect();
+                for depth in 1..=parts.len() {
+                    nodes
+                        .entry(parts[..depth].to_vec())
+                        .or_default()
+                        .count(task);
+                }
+            }
+            None => unfiled.count(task),
+        }
+    }
+
+    let rows: Vec<(String, &Counts)> = nodes
+        .iter()
+        .map(|(parts, counts)| {
+            let indent = "  ".repeat(parts.len() - 1);
+            (format!("{}{}", indent, parts[parts.len() - 1]), counts)
+        })
+        .chain(
+            Some(("(no project)".to_string(), &unfiled))
+                .filter(|(_, c)| c.pending + c.completed > 0),
+        )
+        .collect();
+    let width = rows
+        .iter()
+        .map(|(name, _)| name.chars().count())
+        .max()
+        .unwrap_or(0);
+
+    let header = "Projects";
+    let line_break = (0..header.len()).map(|_| "─").collect::<String>();
+    println!("{}", header);
+    println!("{}", line_break);
+    for (name, counts) in rows {
+        println!(
+            "{:width$}  {} pending, {} completed",
+            name,
+            counts.pending,
+            counts.completed,
+            width = width
+        );
+    }
+}
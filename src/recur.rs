@@ -0,0 +1,322 @@
+use crate::due;
+use crate::error::ParseError;
+use chrono::{DateTime, Datelike, Duration, Local, Months, NaiveDate, NaiveTime, Utc, Weekday};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unit {
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+/// When the next instance of a recurring task falls due.
+///
+/// Parsed from phrases such as `daily`, `every 2 weeks`, `every monday`,
+/// `monthly on the 1st` and `3 days after completion`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Recurrence {
+    /// A fixed interval after the previous due date.
+    Every(u32, Unit),
+    /// The next given day of the week.
+    Weekday(Weekday),
+    /// The given day of the month, or the last day of shorter months.
+    MonthDay(u32),
+    /// A fixed interval after the previous instance was closed.
+    AfterCompletion(u32, Unit),
+}
+
+impl Recurrence {
+    /// The due date of the first instance of a series started at `now`.
+    /// Series counted from completion start without one.
+    pub fn first(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let today = now.with_timezone(&Local).date_naive();
+        let date = match *self {
+            Recurrence::Every(..) => today,
+            Recurrence::Weekday(day) => due::next_weekday(today, day, true),
+            Recurrence::MonthDay(day) => next_month_day(today - Duration::days(1), day),
+            Recurrence::AfterCompletion(..) => return None,
+        };
+        Some(due::end_of_day(date)?.with_timezone(&Utc))
+    }
+
+    /// The due date of the instance after one due at `due` and closed at
+    /// `closed`. It always falls after `closed`, skipping any occurrences
+    /// missed in between, and keeps the time of day of `due`.
+    pub fn next(&self, due: Option<DateTime<Utc>>, closed: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let closed_local = closed.with_timezone(&Local);
+        let time = match due {
+            Some(due) => due.with_timezone(&Local).time(),
+            None => NaiveTime::from_hms_opt(23, 59, 59)?,
+        };
+        let at = |date: NaiveDate| due::local_time(date.and_time(time));
+        let base = due.map_or(closed_local, |d| d.with_timezone(&Local));
+        let after = base.max(closed_local).date_naive();
+        let next = match *self {
+            Recurrence::Every(count, unit) => {
+                let mut date = base.date_naive();
+                loop {
+                    date = advance(date, count, unit)?;
+                    if at(date)? > closed_local {
+                        break at(date)?;
+                    }
+                }
+            }
+            Recurrence::Weekday(day) => at(due::next_weekday(after, day, false))?,
+            Recurrence::MonthDay(day) => at(next_month_day(after, day))?,
+            Recurrence::AfterCompletion(count, unit) => {
+                due::end_of_day(advance(closed_local.date_naive(), count, unit)?)?
+            }
+        };
+        Some(next.with_timezone(&Utc))
+    }
+}
+
+fn advance(date: NaiveDate, count: u32, unit: Unit) -> Option<NaiveDate> {
+    match unit {
+        Unit::Day => date.checked_add_signed(Duration::days(count as i64)),
+        Unit::Week => date.checked_add_signed(Duration::weeks(count as i64)),
+        Unit::Month => date.checked_add_months(Months::new(count)),
+        Unit::Year => date.checked_add_months(Months::new(count.checked_mul(12)?)),
+    }
+}
+
+/// The first date after `date` falling on `day` of its month, using the
+/// last day of months too short to have one.
+fn next_month_day(date: NaiveDate, day: u32) -> NaiveDate {
+    let first = date.with_day(1).expect("every month has a first day");
+    let in_month = |first: NaiveDate| {
+        let last = first + Months::new(1) - Duration::days(1);
+        first.with_day(day.min(last.day())).unwrap_or(last)
+    };
+    let candidate = in_month(first);
+    if candidate > date {
+        candidate
+    } else {
+        in_month(first + Months::new(1))
+    }
+}
+
+fn unit(word: &str) -> Option<Unit> {
+    match word {
+        "day" | "days" => Some(Unit::Day),
+        "week" | "weeks" => Some(Unit::Week),
+        "month" | "months" => Some(Unit::Month),
+        "year" | "years" => Some(Unit::Year),
+        _ => None,
+    }
+}
+
+fn ordinal(word: &str) -> Option<u32> {
+    let digits = word.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let day: u32 = digits.parse().ok()?;
+    (1..=31).contains(&day).then_some(day)
+}
+
+impl FromStr for Recurrence {
+    type Err = ParseError;
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let lower = input.trim().to_lowercase();
+        let words: Vec<&str> = lower.split_whitespace().collect();
+        let count = |word: &str| word.parse::<u32>().ok().filter(|n| *n > 0);
+        let rule = match words.as_slice() {
+            ["daily"] => Some(Recurrence::Every(1, Unit::Day)),
+            ["weekly"] => Some(Recurrence::Every(1, Unit::Week)),
+            ["monthly"] => Some(Recurrence::Every(1, Unit::Month)),
+            ["yearly"] | ["annually"] => Some(Recurrence::Every(1, Unit::Year)),
+            ["every", "other", u] => unit(u).map(|u| Recurrence::Every(2, u)),
+            ["every", word] => match (unit(word), due::weekday(word)) {
+                (Some(u), _) => Some(Recurrence::Every(1, u)),
+                (None, Some(day)) => Some(Recurrence::Weekday(day)),
+                (None, None) => None,
+            },
+            ["every", n, u] => count(n).zip(unit(u)).map(|(n, u)| Recurrence::Every(n, u)),
+            ["monthly", "on", "the", day] | ["every", "month", "on", "the", day] => {
+                ordinal(day).map(Recurrence::MonthDay)
+            }
+            [n, u, "after", "completion"] => count(n)
+                .zip(unit(u))
+                .map(|(n, u)| Recurrence::AfterCompletion(n, u)),
+            _ => None,
+        };
+        rule.ok_or_else(|| ParseError::new("recurrence", input))
+    }
+}
+
+fn unit_name(count: u32, unit: Unit) -> String {
+    let name = match unit {
+        Unit::Day => "day",
+        Unit::Week => "week",
+        Unit::Month => "month",
+        Unit::Year => "year",
+    };
+    if count == 1 {
+        name.to_string()
+    } else {
+        format!("{} {}s", count, name)
+    }
+}
+
+impl fmt::Display for Recurrence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Recurrence::Every(count, unit) => write!(f, "every {}", unit_name(count, unit)),
+            Recurrence::Weekday(day) => {
+                let name = match day {
+                    Weekday::Mon => "monday",
+                    Weekday::Tue => "tuesday",
+                    Weekday::Wed => "wednesday",
+                    Weekday::Thu => "thursday",
+                    Weekday::Fri => "friday",
+                    Weekday::Sat => "saturday",
+                    Weekday::Sun => "sunday",
+                };
+                write!(f, "every {}", name)
+            }
+            Recurrence::MonthDay(day) => {
+                let suffix = match (day % 10, day % 100) {
+                    (_, 11..=13) => "th",
+                    (1, _) => "st",
+                    (2, _) => "nd",
+                    (3, _) => "rd",
+                    _ => "th",
+                };
+                write!(f, "monthly on the {}{}", day, suffix)
+            }
+            Recurrence::AfterCompletion(count, unit) => {
+                let amount = if count == 1 {
+                    format!("1 {}", unit_name(1, unit))
+                } else {
+                    unit_name(count, unit)
+                };
+                write!(f, "{} after completion", amount)
+            }
+        }
+    }
+}
+
+/// Stored as the same phrase it is written as.
+impl Serialize for Recurrence {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Recurrence {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        String::deserialize(d)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Utc> {
+        due::local_time(
+            NaiveDate::from_ymd_opt(y, m, d)
+                .and_then(|d| d.and_hms_opt(h, min, 0))
+                .unwrap(),
+        )
+        .unwrap()
+        .with_timezone(&Utc)
+    }
+
+    fn shown(time: Option<DateTime<Utc>>) -> String {
+        time.unwrap()
+            .with_timezone(&Local)
+            .format("%Y-%m-%d %H:%M")
+            .to_string()
+    }
+
+    fn rule(input: &str) -> Recurrence {
+        input.parse().unwrap_or_else(|e| panic!("{}: {}", input, e))
+    }
+
+    #[test]
+    fn first_instances() {
+        // Friday 2026-10-16.
+        let now = at(2026, 10, 16, 10, 30);
+        assert_eq!(shown(rule("daily").first(now)), "2026-10-16 23:59");
+        assert_eq!(shown(rule("every friday").first(now)), "2026-10-16 23:59");
+        assert_eq!(shown(rule("every monday").first(now)), "2026-10-19 23:59");
+        assert_eq!(
+            shown(rule("monthly on the 16th").first(now)),
+            "2026-10-16 23:59"
+        );
+        assert_eq!(
+            shown(rule("monthly on the 1st").first(now)),
+            "2026-11-01 23:59"
+        );
+        assert_eq!(rule("3 days after completion").first(now), None);
+    }
+
+    #[test]
+    fn month_days_clamp_to_short_months() {
+        let monthly = rule("monthly on the 31st");
+        let due = at(2027, 1, 31, 9, 0);
+        let feb = monthly.next(Some(due), at(2027, 1, 31, 12, 0));
+        assert_eq!(shown(feb), "2027-02-28 09:00");
+        let march = monthly.next(feb, at(2027, 2, 28, 12, 0));
+        assert_eq!(shown(march), "2027-03-31 09:00");
+
+        let every = rule("monthly");
+        assert_eq!(
+            shown(every.next(Some(due), at(2027, 1, 31, 12, 0))),
+            "2027-02-28 09:00"
+        );
+    }
+
+    #[test]
+    fn skips_missed_occurrences() {
+        let due = at(2026, 10, 1, 8, 0);
+        let closed = at(2026, 10, 16, 10, 30);
+        assert_eq!(
+            shown(rule("daily").next(Some(due), closed)),
+            "2026-10-17 08:00"
+        );
+        assert_eq!(
+            shown(rule("every 2 weeks").next(Some(due), closed)),
+            "2026-10-29 08:00"
+        );
+        assert_eq!(
+            shown(rule("every monday").next(Some(due), closed)),
+            "2026-10-19 08:00"
+        );
+        assert_eq!(
+            shown(rule("monthly on the 5th").next(Some(due), closed)),
+            "2026-11-05 08:00"
+        );
+        let early = at(2026, 9, 28, 12, 0);
+        assert_eq!(
+            shown(rule("weekly").next(Some(due), early)),
+            "2026-10-08 08:00"
+        );
+    }
+
+    #[test]
+    fn counts_from_completion() {
+        let due = at(2026, 10, 1, 8, 0);
+        let closed = at(2026, 10, 16, 10, 30);
+        let rule = rule("3 days after completion");
+        assert_eq!(shown(rule.next(Some(due), closed)), "2026-10-19 23:59");
+        assert_eq!(shown(rule.next(None, closed)), "2026-10-19 23:59");
+    }
+
+    #[test]
+    fn overflowing_intervals_have_no_next_instance() {
+        let closed = at(2026, 10, 16, 10, 30);
+        for input in ["every 4000000000 years", "every 4000000000 days"] {
+            assert_eq!(rule(input).next(Some(closed), closed), None, "{}", input);
+        }
+        assert_eq!(
+            rule("4000000000 years after completion").next(None, closed),
+            None
+        );
+    }
+}
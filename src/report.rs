@@ -0,0 +1,221 @@
+use crate::due;
+use crate::error::{Error, ParseError};
+use crate::task::Task;
+use chrono::{DateTime, Datelike, Duration, Local, Utc};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+/// What `report` totals tracked time by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Group {
+    Task,
+    Tag,
+    Project,
+}
+
+impl FromStr for Group {
+    type Err = ParseError;
+    fn from_str(group: &str) -> Result<Self, Self::Err> {
+        match group {
+            "task" => Ok(Group::Task),
+            "tag" => Ok(Group::Tag),
+            "project" => Ok(Group::Project),
+            _ => Err(ParseError::new("report grouping", group)),
+        }
+    }
+}
+
+impl Group {
+    const ALL: [Group; 3] = [Group::Task, Group::Tag, Group::Project];
+
+    fn title(self) -> &'static str {
+        match self {
+            Group::Task => "Tasks",
+            Group::Tag => "Tags",
+            Group::Project => "Projects",
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Group::Task => "task",
+            Group::Tag => "tag",
+            Group::Project => "project",
+        }
+    }
+
+    /// The rows a task's time counts towards, keyed so that tasks sort by
+    /// ID. A task with several tags counts in full towards each of them.
+    fn keys(self, task: &Task) -> Vec<(usize, String)> {
+        match self {
+            Group::Task => vec![(task.id, format!("{} - {}", task.id, task.description))],
+            Group::Tag if task.tags.is_empty() => vec![(0, "(no tag)".to_string())],
+            Group::Tag => task.tags.iter().map(|t| (0, format!("+{}", t))).collect(),
+            Group::Project => vec![(
+                0,
+                task.project
+                    .clone()
+                    .unwrap_or_else(|| "(no project)".to_string()),
+            )],
+        }
+    }
+}
+
+/// The span from the start of the day `from` falls on to the end of the day
+/// `to` falls on. Without them it is the current week so far, from Monday.
+/// A span ending before it starts is refused rather than reported empty.
+pub fn day_range(
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+) -> Result<(DateTime<Utc>, DateTime<Utc>), Error> {
+    let today = Local::now().date_naive();
+    let monday = today - Duration::days(today.weekday().num_days_from_monday() as i64);
+    let day = |time: DateTime<Utc>| time.with_timezone(&Local).date_naive();
+    let (first, last) = (from.map_or(monday, day), to.map_or(today, day));
+    if first > last {
+        return Err(Error::Validation(format!(
+            "The report would start on {} after it ends on {}",
+            first, last
+        )));
+    }
+    let start = first.and_hms_opt(0, 0, 0);
+    let start = start.and_then(due::local_time).expect("midnight exists");
+    let end = due::end_of_day(last).expect("the end of a day exists");
+    Ok((start.with_timezone(&Utc), end.with_timezone(&Utc)))
+}
+
+/// Formats a duration as hours and minutes, e.g. `2h 05m`.
+pub fn format_duration(duration: Duration) -> String {
+    let minutes = (duration.num_seconds() + 30) / 60;
+    if minutes < 60 {
+        format!("{}m", minutes)
+    } else {
+        format!("{}h {:02}m", minutes / 60, minutes % 60)
+    }
+}
+
+/// Totals the time tracked on `tasks` between `from` and `to`, for each
+/// grouping in `groups`, and prints it as tables or as CSV with one row per
+/// group and name.
+pub fn print(tasks: &[&Task], from: DateTime<Utc>, to: DateTime<Utc>, groups: &[Group], csv: bool) {
+    let now = Utc::now();
+    let tracked: Vec<(&Task, Duration)> = tasks
+        .iter()
+        .map(|task| (*task, task.tracked(from, to, now)))
+        .filter(|(_, time)| *time > Duration::zero())
+        .collect();
+    let total = tracked
+        .iter()
+        .fold(Duration::zero(), |sum, (_, time)| sum + *time);
+    let groups = if groups.is_empty() {
+        &Group::ALL[..]
+    } else {
+        groups
+    };
+    if csv {
+        println!("group,name,hours");
+    }
+    for (index, group) in groups.iter().enumerate() {
+        let mut totals: BTreeMap<(usize, String), Duration> = BTreeMap::new();
+        for (task, time) in &tracked {
+            for key in group.keys(task) {
+                *totals.entry(key).or_insert_with(Duration::zero) += *time;
+            }
+        }
+        if csv {
+            for ((_, name), time) in totals {
+                let hours = time.num_seconds() as f64 / 3600.0;
+                println!("{},{},{:.2}", group.name(), csv_field(&name), hours);
+            }
+            continue;
+        }
+        if index > 0 {
+            println!();
+        }
+        print_table(group.title(), from, to, &totals, total);
+    }
+}
+
+/// Prints one grouping. `total` is the time tracked overall, which for tags
+/// can be less than the sum of the rows.
+fn print_table(
+    title: &str,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    totals: &BTreeMap<(usize, String), Duration>,
+    total: Duration,
+) {
+    let range = format!(
+        "{} to {}",
+        from.with_timezone(&Local).format("%Y-%m-%d"),
+        to.with_timezone(&Local).format("%Y-%m-%d")
+    );
+    let header = format!("{} ({})", title, range);
+    println!("{}", header);
+    println!(
+        "{}",
+        (0..header.chars().count()).map(|_| "─").collect::<String>()
+    );
+    if totals.is_empty() {
+        println!("No time tracked");
+        return;
+    }
+    let width = totals
+        .keys()
+        .map(|(_, name)| name.chars().count())
+        .chain(Some("Total".len()))
+        .max()
+        .unwrap_or(0);
+    for ((_, name), time) in totals {
+        println!(
+            "{:width$}  {:>8}",
+            name,
+            format_duration(*time),
+            width = width
+        );
+    }
+    println!(
+        "{:width$}  {:>8}",
+        "Total",
+        format_duration(total),
+        width = width
+    );
+}
+
+/// Quotes a CSV field if it holds a comma, quote or line break.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(date: &str) -> DateTime<Utc> {
+        due::parse(date).unwrap()
+    }
+
+    #[test]
+    fn day_range_spans_whole_days() {
+        let (from, to) = day_range(Some(day("2026-10-12")), Some(day("2026-10-16"))).unwrap();
+        let local = |time: DateTime<Utc>| time.with_timezone(&Local).format("%F %T").to_string();
+        assert_eq!(local(from), "2026-10-12 00:00:00");
+        assert_eq!(local(to), "2026-10-16 23:59:59");
+        assert!(day_range(Some(day("2026-10-16")), Some(day("2026-10-16"))).is_ok());
+    }
+
+    #[test]
+    fn day_range_refuses_inverted_spans() {
+        match day_range(Some(day("2026-10-19")), Some(day("2026-10-16"))) {
+            Err(Error::Validation(message)) => assert_eq!(
+                message,
+                "The report would start on 2026-10-19 after it ends on 2026-10-16"
+            ),
+            other => panic!("expected a validation error, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,332 @@
+use crate::error::Error;
+use chrono::{SecondsFormat, TimeZone, Utc};
+use serde_json::{json, Map, Value};
+use std::path::Path;
+
+/// The schema version this build reads and writes. When the stored format
+/// changes, bump it and append a step to `MIGRATIONS`.
+///
+/// - 0: a bare array of tasks, identified by position
+/// - 1: `{"next_id", "tasks"}` with persistent IDs; tasks may still hold
+///   fields from before 2, such as `time` and single-character statuses
+/// - 2: `{"version", "next_id", "tasks"}`
+/// - 3: tasks may be recurring templates, with `recur`, or their
+///   instances, with `template`
+/// - 4: tasks may have a `parent` and `depends_on` other tasks
+/// - 5: tasks may have `annotations` and `notes`
+/// - 6: tasks may have tracked time `intervals`
+/// - 7: tasks may record when they were `restored` from the archive
+pub const VERSION: u64 = 7;
+
+/// Upgrades a document in place by one version, returning a line for each
+/// kind of change it made.
+type Migration = fn(&mut Value) -> Vec<String>;
+
+/// `MIGRATIONS[n]` upgrades a version `n` document to version `n + 1`.
+const MIGRATIONS: [Migration; VERSION as usize] = [
+    number_tasks,
+    convert_fields,
+    no_changes,
+    no_changes,
+    no_changes,
+    no_changes,
+    no_changes,
+];
+
+/// The schema upgrades applied to a document as it was loaded.
+#[derive(Debug)]
+pub struct Upgrade {
+    pub from: u64,
+    pub notes: Vec<String>,
+}
+
+/// Brings `document`, read from `path`, up to `VERSION`. Documents from a
+/// newer build are refused, since saving them would drop whatever that
+/// build added.
+pub fn upgrade(path: &Path, document: &mut Value) -> Result<Upgrade, Error> {
+    let from = match document {
+        Value::Array(_) => 0,
+        Value::Object(map) => match map.get("version") {
+            None => 1,
+            Some(version) => version.as_u64().ok_or_else(|| {
+                Error::Validation(format!(
+                    "{}: `version` must be a whole number, not {}",
+                    path.display(),
+                    version
+                ))
+            })?,
+        },
+        _ => VERSION,
+    };
+    if from > VERSION {
+        return Err(Error::Validation(format!(
+            "{} uses schema version {}, but this build of todo only reads up to {}; \
+             upgrade todo to use it",
+            path.display(),
+            from,
+            VERSION
+        )));
+    }
+    let mut notes = Vec::new();
+    for (version, migration) in MIGRATIONS.iter().enumerate().skip(from as usize) {
+        for note in migration(document) {
+            notes.push(format!("version {} → {}: {}", version, version + 1, note));
+        }
+    }
+    if let Value::Object(map) = document {
+        map.insert("version".to_string(), json!(VERSION));
+    }
+    Ok(Upgrade { from, notes })
+}
+
+fn tasks_mut(document: &mut Value) -> impl Iterator<Item = &mut Map<String, Value>> {
+    document
+        .get_mut("tasks")
+        .and_then(Value::as_array_mut)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_object_mut)
+}
+
+fn plural(count: usize, noun: &str) -> String {
+    format!("{} {}{}", count, noun, if count == 1 { "" } else { "s" })
+}
+
+/// 0 → 1: numbers the tasks 1..N in file order.
+fn number_tasks(document: &mut Value) -> Vec<String> {
+    let mut tasks = match document.take() {
+        Value::Array(tasks) => tasks,
+        other => {
+            *document = other;
+            return Vec::new();
+        }
+    };
+    for (index, task) in tasks.iter_mut().enumerate() {
+        if let Value::Object(task) = task {
+            task.insert("id".to_string(), json!(index + 1));
+        }
+    }
+    let count = tasks.len();
+    *document = json!({ "next_id": count + 1, "tasks": tasks });
+    vec![format!("numbered {} 1 to {}", plural(count, "task"), count)]
+}
+
+/// 1 → 2: renames `time` to `created`, converting epoch milliseconds to
+/// RFC 3339; names single-character statuses; and turns `reopenings` into
+/// a status `history`.
+fn convert_fields(document: &mut Value) -> Vec<String> {
+    let (mut times, mut statuses, mut reopenings) = (0, 0, 0);
+    for task in tasks_mut(document) {
+        if let Some(time) = task.remove("time") {
+            let created = match time.as_i64() {
+                Some(millis) => Utc
+                    .timestamp_millis_opt(millis)
+                    .single()
+                    .map(|t| json!(t.to_rfc3339_opts(SecondsFormat::AutoSi, true)))
+                    .unwrap_or(time),
+                None => time,
+            };
+            task.entry("created").or_insert(created);
+            times += 1;
+        }
+        let status = match task.get("status").and_then(Value::as_str) {
+            Some(" ") => Some("pending"),
+            Some("✓") => Some("completed"),
+            _ => None,
+        };
+        if let Some(status) = status {
+            task.insert("status".to_string(), json!(status));
+            statuses += 1;
+        }
+        if let Some(Value::Array(entries)) = task.remove("reopenings") {
+            let mut history = Vec::new();
+            for entry in entries {
+                if let Some(completed) = entry.get("completed").filter(|c| !c.is_null()) {
+                    history.push(json!({ "from": "pending", "to": "completed", "at": completed }));
+                }
+                if let Some(reopened) = entry.get("reopened") {
+                    history.push(json!({ "from": "completed", "to": "pending", "at": reopened }));
+                }
+            }
+            task.insert("history".to_string(), Value::Array(history));
+            reopenings += 1;
+        }
+    }
+    let mut notes = Vec::new();
+    if times > 0 {
+        notes.push(format!(
+            "renamed `time` to `created` in {}",
+            plural(times, "task")
+        ));
+    }
+    if statuses > 0 {
+        notes.push(format!("named the status of {}", plural(statuses, "task")));
+    }
+    if reopenings > 0 {
+        notes.push(format!(
+            "converted `reopenings` to `history` in {}",
+            plural(reopenings, "task")
+        ));
+    }
+    notes
+}
+
+/// For versions that only add fields, so that older builds refuse files
+/// they would otherwise save without them.
+fn no_changes(_: &mut Value) -> Vec<String> {
+    Vec::new()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::status::Status;
+    use crate::TaskList;
+    use chrono::{DateTime, Utc};
+
+    fn upgraded(document: &str) -> (TaskList, Upgrade) {
+        let mut document: Value = serde_json::from_str(document).unwrap();
+        let upgrade = upgrade(Path::new("todo.json"), &mut document).unwrap();
+        assert_eq!(document["version"], json!(VERSION));
+        let list = serde_json::from_value(document).unwrap();
+        (list, upgrade)
+    }
+
+    fn time(rfc3339: &str) -> DateTime<Utc> {
+        rfc3339.parse().unwrap()
+    }
+
+    #[test]
+    fn upgrades_a_bare_array() {
+        let (list, upgrade) = upgraded(
+            r#"[
+                {"time": 1700000000000, "description": "first", "status": " "},
+                {"time": 1700000100500, "description": "second", "status": "✓"}
+            ]"#,
+        );
+        assert_eq!(upgrade.from, 0);
+        assert_eq!(
+            upgrade.notes,
+            [
+                "version 0 → 1: numbered 2 tasks 1 to 2",
+                "version 1 → 2: renamed `time` to `created` in 2 tasks",
+                "version 1 → 2: named the status of 2 tasks",
+            ]
+        );
+        assert_eq!(list.next_id, 3);
+        let first = &list.tasks[&1];
+        assert_eq!(first.description, "first");
+        assert_eq!(first.status, Status::Pending);
+        assert_eq!(first.created, time("2023-11-14T22:13:20Z"));
+        let second = &list.tasks[&2];
+        assert_eq!(second.description, "second");
+        assert_eq!(second.status, Status::Completed);
+        assert_eq!(second.created, time("2023-11-14T22:15:00.500Z"));
+        assert!(second.history.is_empty());
+    }
+
+    #[test]
+    fn upgrades_reopenings_to_history() {
+        let (list, upgrade) = upgraded(
+            r#"{"next_id": 8, "tasks": [
+                {"id": 7, "time": 1700000000000, "description": "again", "status": "✓",
+                 "reopenings": [
+                    {"completed": "2023-11-15T09:00:00Z", "reopened": "2023-11-16T09:00:00Z"},
+                    {"completed": null, "reopened": "2023-11-17T09:00:00Z"},
+                    {"completed": "2023-11-18T09:00:00Z"}
+                 ]},
+                {"id": 3, "created": "2023-11-01T00:00:00Z", "description": "new already",
+                 "status": "in-progress"}
+            ]}"#,
+        );
+        assert_eq!(upgrade.from, 1);
+        assert_eq!(
+            upgrade.notes,
+            [
+                "version 1 → 2: renamed `time` to `created` in 1 task",
+                "version 1 → 2: named the status of 1 task",
+                "version 1 → 2: converted `reopenings` to `history` in 1 task",
+            ]
+        );
+        assert_eq!(list.next_id, 8);
+        assert_eq!(list.tasks.keys().copied().collect::<Vec<_>>(), [3, 7]);
+        let again = &list.tasks[&7];
+        let history: Vec<(Status, Status, DateTime<Utc>)> = again
+            .history
+            .iter()
+            .map(|change| (change.from, change.to, change.at))
+            .collect();
+        assert_eq!(
+            history,
+            [
+                (
+                    Status::Pending,
+                    Status::Completed,
+                    time("2023-11-15T09:00:00Z")
+                ),
+                (
+                    Status::Completed,
+                    Status::Pending,
+                    time("2023-11-16T09:00:00Z")
+                ),
+                (
+                    Status::Completed,
+                    Status::Pending,
+                    time("2023-11-17T09:00:00Z")
+                ),
+                (
+                    Status::Pending,
+                    Status::Completed,
+                    time("2023-11-18T09:00:00Z")
+                ),
+            ]
+        );
+        assert_eq!(list.tasks[&3].status, Status::InProgress);
+    }
+
+    #[test]
+    fn leaves_a_current_document_alone() {
+        let document = format!(
+            r#"{{"version": {}, "next_id": 2, "tasks": [
+                {{"id": 1, "created": "2023-11-01T00:00:00Z", "description": "x",
+                  "status": "pending"}}
+            ]}}"#,
+            VERSION
+        );
+        let mut value: Value = serde_json::from_str(&document).unwrap();
+        let before = value.clone();
+        let upgrade = upgrade(Path::new("todo.json"), &mut value).unwrap();
+        assert_eq!(upgrade.from, VERSION);
+        assert!(upgrade.notes.is_empty());
+        assert_eq!(value, before);
+    }
+
+    #[test]
+    fn refuses_newer_and_malformed_versions() {
+        let refused = |document: Value| {
+            let mut document = document;
+            let before = document.clone();
+            let error = upgrade(Path::new("todo.json"), &mut document).unwrap_err();
+            assert_eq!(document, before, "a refused document is left as it was");
+            match error {
+                Error::Validation(message) => message,
+                other => panic!("expected a validation error, got {:?}", other),
+            }
+        };
+        let newer = refused(json!({"version": VERSION + 1, "next_id": 1, "tasks": []}));
+        assert_eq!(
+            newer,
+            format!(
+                "todo.json uses schema version {}, but this build of todo only reads up to {}; \
+                 upgrade todo to use it",
+                VERSION + 1,
+                VERSION
+            )
+        );
+        let malformed = refused(json!({"version": "two", "next_id": 1, "tasks": []}));
+        assert_eq!(
+            malformed,
+            "todo.json: `version` must be a whole number, not \"two\""
+        );
+    }
+}
@@ -0,0 +1,339 @@
+use crate::color::Color;
+use crate::config::Config;
+use crate::task::Task;
+use chrono::Local;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Where in a task a word was found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Field {
+    Description,
+    /// The annotation at this position.
+    Annotation(usize),
+    Notes,
+}
+
+impl Field {
+    /// How much a match here counts towards a task's rank.
+    fn weight(self) -> f64 {
+        match self {
+            Field::Description => 3.0,
+            Field::Annotation(_) => 2.0,
+            Field::Notes => 1.0,
+        }
+    }
+}
+
+/// Splits text into lowercase words of letters and digits.
+fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+}
+
+/// An inverted index from each word to the tasks and fields it appears in,
+/// so that a search only visits the tasks sharing a word with the query.
+pub struct Index<'a> {
+    tasks: BTreeMap<usize, &'a Task>,
+    words: BTreeMap<String, BTreeSet<(usize, Field)>>,
+    /// The words again, by length in characters, so that fuzzy matching
+    /// only compares words that could be close enough.
+    lengths: BTreeMap<usize, BTreeSet<String>>,
+}
+
+/// A task matching every word of a query, with the index words it matched.
+pub struct Hit<'a> {
+    pub task: &'a Task,
+    pub score: f64,
+    matched: BTreeSet<String>,
+    fields: BTreeSet<Field>,
+}
+
+impl<'a> Index<'a> {
+    pub fn build(tasks: impl IntoIterator<Item = &'a Task>) -> Index<'a> {
+        let mut index = Index {
+            tasks: BTreeMap::new(),
+            words: BTreeMap::new(),
+            lengths: BTreeMap::new(),
+        };
+        for task in tasks {
+            index.add(task.id, Field::Description, &task.description);
+            for (position, annotation) in task.annotations.iter().enumerate() {
+                index.add(task.id, Field::Annotation(position), &annotation.text);
+            }
+            if let Some(notes) = &task.notes {
+                index.add(task.id, Field::Notes, notes);
+            }
+            index.tasks.insert(task.id, task);
+        }
+        index
+    }
+
+    fn add(&mut self, id: usize, field: Field, text: &str) {
+        for word in words(text) {
+            let length = word.chars().count();
+            self.lengths.entry(length).or_default().insert(word.clone());
+            self.words.entry(word).or_default().insert((id, field));
+        }
+    }
+
+    /// The index words a query word matches, with how closely: exactly,
+    /// as a prefix, or within a small edit distance for longer words.
+    /// Prefixes are found in the sorted index directly. Words within the
+    /// edit distance differ in length by at most that much, so only words of
+    /// those lengths are compared; that part is a scan, but of a small share
+    /// of the index.
+    fn expand(&self, query: &str) -> Vec<(&str, f64)> {
+        let mut found: Vec<(&str, f64)> = self
+            .words
+            .range(query.to_string()..)
+            .take_while(|(word, _)| word.starts_with(query))
+            .map(|(word, _)| (word.as_str(), if word == query { 1.0 } else { 0.7 }))
+            .collect();
+        let length = query.chars().count();
+        let allowed = match length {
+            0..=3 => return found,
+            4..=7 => 1,
+            _ => 2,
+        };
+        let nearby = self.lengths.range(length - allowed..=length + allowed);
+        for word in nearby.flat_map(|(_, words)| words) {
+            if word.starts_with(query) {
+                continue;
+            }
+            if let Some(distance) = edit_distance(query, word, allowed) {
+                found.push((word.as_str(), 0.5 / distance as f64));
+            }
+        }
+        found
+    }
+
+    /// Tasks matching every word of `query`, best first. A task's score adds
+    /// up, for each query word, its closest match weighted by the field it
+    /// was found in.
+    pub fn search(&self, query: &str) -> Vec<Hit<'a>> {
+        let mut hits: Option<BTreeMap<usize, Hit<'a>>> = None;
+        for query_word in words(query) {
+            let mut best: BTreeMap<usize, Hit<'a>> = BTreeMap::new();
+            for (word, closeness) in self.expand(&query_word) {
+                for &(id, field) in &self.words[word] {
+                    let hit = best.entry(id).or_insert_with(|| Hit {
+                        task: self.tasks[&id],
+                        score: 0.0,
+                        matched: BTreeSet::new(),
+                        fields: BTreeSet::new(),
+                    });
+                    hit.score = hit.score.max(closeness * field.weight());
+                    hit.matched.insert(word.to_string());
+                    hit.fields.insert(field);
+                }
+            }
+            hits = Some(match hits {
+                None => best,
+                Some(previous) => previous
+                    .into_iter()
+                    .filter_map(|(id, mut hit)| {
+                        let next = best.remove(&id)?;
+                        hit.score += next.score;
+                        hit.matched.extend(next.matched);
+                        hit.fields.extend(next.fields);
+                        Some((id, hit))
+                    })
+                    .collect(),
+            });
+        }
+        let mut hits: Vec<Hit<'a>> = hits.unwrap_or_default().into_values().collect();
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.task.id.cmp(&b.task.id)));
+        hits
+    }
+}
+
+/// The Levenshtein distance between `a` and `b`, if it is at most `limit`.
+fn edit_distance(a: &str, b: &str, limit: usize) -> Option<usize> {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    if a.len().abs_diff(b.len()) > limit {
+        return None;
+    }
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        if current.iter().min().is_some_and(|&d| d > limit) {
+            return None;
+        }
+        previous = current;
+    }
+    Some(previous[b.len()]).filter(|&d| d <= limit)
+}
+
+/// Marks the words of `text` that are in `matched`, in color or else
+/// between asterisks.
+fn highlight(text: &str, matched: &BTreeSet<String>, color: Option<Color>) -> String {
+    let mut out = String::new();
+    let mut word = String::new();
+    let flush = |word: &mut String, out: &mut String| {
+        if matched.contains(&word.to_lowercase()) {
+            match color {
+                Some(color) => out.push_str(&color.paint(word)),
+                None => out.push_str(&format!("*{}*", word)),
+            }
+        } else {
+            out.push_str(word);
+        }
+        word.clear();
+    };
+    for c in text.chars() {
+        if c.is_alphanumeric() {
+            word.push(c);
+        } else {
+            flush(&mut word, &mut out);
+            out.push(c);
+        }
+    }
+    flush(&mut word, &mut out);
+    out
+}
+
+/// Prints up to `limit` hits, each with the annotations and lines of notes
+/// it matched in. Hits for which `is_archived` holds are marked as such.
+pub fn print<F>(
+    query: &str,
+    hits: &[Hit],
+    limit: usize,
+    config: &Config,
+    color: bool,
+    is_archived: F,
+) where
+    F: Fn(usize) -> bool,
+{
+    let header = format!(
+        "Search: {} ({} {})",
+        query,
+        hits.len(),
+        if hits.len() == 1 { "match" } else { "matches" }
+    );
+    println!("{}", header);
+    println!(
+        "{}",
+        (0..header.chars().count()).map(|_| "─").collect::<String>()
+    );
+    let highlight_color = color.then_some(config.theme.highlight);
+    for hit in hits.iter().take(limit) {
+        let task = hit.task;
+        let mark = |text: &str| highlight(text, &hit.matched, highlight_color);
+        let archived = if is_archived(task.id) {
+            " (archived)"
+        } else {
+            ""
+        };
+        println!(
+            "{} - {} [{}]{}",
+            task.id,
+            mark(&task.description),
+            config.glyphs.get(task.status),
+            archived
+        );
+        for field in &hit.fields {
+            match *field {
+                Field::Description => {}
+                Field::Annotation(position) => {
+                    let annotation = &task.annotations[position];
+                    let at = annotation.at.with_timezone(&Local).format("%Y-%m-%d");
+                    println!("    {}: {}", at, mark(&annotation.text));
+                }
+                Field::Notes => {
+                    let notes = task.notes.as_deref().unwrap_or_default();
+                    for line in notes.lines() {
+                        if words(line).any(|word| hit.matched.contains(&word)) {
+                            println!("    notes: {}", mark(line.trim()));
+                        }
+                    }
+                }
+            }
+        }
+    }
+    if hits.len() > limit {
+        println!("… {} more; raise --limit to see them", hits.len() - limit);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(id: usize, description: &str, annotation: Option<&str>, notes: Option<&str>) -> Task {
+        let mut task = Task::new(description.to_string());
+        task.id = id;
+        if let Some(text) = annotation {
+            task.annotate(text.to_string());
+        }
+        task.notes = notes.map(str::to_string);
+        task
+    }
+
+    fn ids(index: &Index, query: &str) -> Vec<usize> {
+        index.search(query).iter().map(|hit| hit.task.id).collect()
+    }
+
+    #[test]
+    fn edit_distance_stops_at_the_limit() {
+        assert_eq!(edit_distance("report", "report", 1), Some(0));
+        assert_eq!(edit_distance("raport", "report", 1), Some(1));
+        assert_eq!(edit_distance("kitten", "sitting", 3), Some(3));
+        assert_eq!(edit_distance("kitten", "sitting", 2), None);
+        assert_eq!(edit_distance("reprot", "report", 1), None);
+        assert_eq!(edit_distance("deploy", "deployment", 2), None);
+        assert_eq!(edit_distance("", "ab", 2), Some(2));
+    }
+
+    #[test]
+    fn fuzzy_matches_scale_with_query_length() {
+        let tasks = [
+            task(1, "Write the report", None, None),
+            task(2, "Plan the deployment", None, None),
+        ];
+        let index = Index::build(&tasks);
+        assert_eq!(ids(&index, "rep"), [1], "short words match as prefixes");
+        assert!(ids(&index, "rpt").is_empty(), "short words are not fuzzy");
+        assert_eq!(ids(&index, "raport"), [1], "one edit in a word of 4 to 7");
+        assert!(ids(&index, "rapert").is_empty(), "but not two");
+        assert_eq!(ids(&index, "deploymnet"), [2], "two edits in a longer word");
+        assert!(ids(&index, "dpeloymnet").is_empty(), "but not three");
+    }
+
+    #[test]
+    fn ranks_by_field_and_closeness() {
+        let tasks = [
+            task(1, "Call the bank", None, Some("ask about the budget")),
+            task(2, "Call the plumber", Some("budget is tight"), None),
+            task(3, "Budget for next year", None, None),
+            task(4, "Buy budgeting book", None, None),
+        ];
+        let index = Index::build(&tasks);
+        let hits = index.search("budget");
+        let ranked: Vec<usize> = hits.iter().map(|hit| hit.task.id).collect();
+        assert_eq!(ranked, [3, 4, 2, 1]);
+        assert_eq!(hits[0].score, 3.0);
+        assert_eq!(hits[2].score, 2.0);
+        assert_eq!(hits[3].score, 1.0);
+    }
+
+    #[test]
+    fn every_query_word_must_match() {
+        let tasks = [
+            task(1, "Buy milk", None, None),
+            task(2, "Buy bread", None, Some("and milk if they have it")),
+            task(3, "Bake bread", None, None),
+        ];
+        let index = Index::build(&tasks);
+        assert_eq!(ids(&index, "milk"), [1, 2]);
+        assert_eq!(ids(&index, "bread milk"), [2]);
+        assert_eq!(ids(&index, "BUY Bread"), [2]);
+        assert!(ids(&index, "bake milk").is_empty());
+        assert!(ids(&index, "").is_empty());
+    }
+}
@@ -0,0 +1,143 @@
+use crate::color::Color;
+use crate::config::Config;
+use crate::task::Task;
+use crate::{due, links, report, TaskList};
+use chrono::{DateTime, Local, Utc};
+
+/// Prints everything known about `task`: its fields, how it relates to
+/// other tasks in `tasks`, the list it is in, its status history,
+/// annotations and notes.
+pub fn print(task: &Task, tasks: &TaskList, archived: bool, config: &Config, color: bool) {
+    let paint = |c: Color, text: &str| {
+        if color {
+            c.paint(text)
+        } else {
+            text.to_string()
+        }
+    };
+    let when = |time: DateTime<Utc>| match &config.date_format {
+        Some(format) => format.format(time),
+        None => time
+            .with_timezone(&Local)
+            .format("%Y-%m-%d %H:%M")
+            .to_string(),
+    };
+    let summary = |id: &usize| match tasks.tasks.get(id) {
+        Some(other) => format!(
+            "{} - {} [{}]",
+            other.id,
+            other.description,
+            config.glyphs.get(other.status)
+        ),
+        None => format!("{} (removed)", id),
+    };
+    let now = Utc::now();
+
+    let mut header = format!("Task {} - {}", task.id, task.description);
+    if archived {
+        header.push_str(" (archived)");
+    }
+    println!("{}", paint(Color::Bold, &header));
+    println!(
+        "{}",
+        (0..header.chars().count()).map(|_| "─").collect::<String>()
+    );
+
+    let mut rows: Vec<(&str, String)> = vec![("Status", task.status.to_string())];
+    if let Some(priority) = task.priority {
+        rows.push((
+            "Priority",
+            paint(
+                config.theme.priority,
+                &format!("{:?}", priority).to_lowercase(),
+            ),
+        ));
+    }
+    if let Some(time) = task.due {
+        let due_color = if task.is_overdue(now) {
+            config.theme.overdue
+        } else {
+            config.theme.due
+        };
+        let text = format!("{} ({})", when(time), due::humanize(time, now));
+        rows.push(("Due", paint(due_color, &text)));
+    }
+    if let Some(project) = &task.project {
+        rows.push(("Project", project.clone()));
+    }
+    if !task.tags.is_empty() {
+        let tags = task
+            .tags
+            .iter()
+            .map(|tag| paint(config.theme.tag, &format!("+{}", tag)))
+            .collect::<Vec<_>>()
+            .join(" ");
+        rows.push(("Tags", tags));
+    }
+    if let Some(recur) = task.recur {
+        rows.push(("Recurs", recur.to_string()));
+    }
+    if let Some(template) = task.template {
+        rows.push(("Series", summary(&template)));
+    }
+    if let Some(parent) = task.parent {
+        rows.push(("Parent", summary(&parent)));
+    }
+    for subtask in tasks.tasks.values().filter(|t| t.parent == Some(task.id)) {
+        rows.push(("Subtask", summary(&subtask.id)));
+    }
+    for dependency in &task.depends_on {
+        rows.push(("Depends on", summary(dependency)));
+    }
+    if links::is_blocked(task, &tasks.tasks) {
+        rows.push(("Blocked", "yes".to_string()));
+    }
+    if !task.intervals.is_empty() {
+        let tracked = task.tracked(task.created, now, now);
+        let mut text = report::format_duration(tracked);
+        if task.is_active() {
+            text.push_str(" (active)");
+        }
+        rows.push(("Tracked", text));
+    }
+    rows.push(("Created", when(task.created)));
+    if let Some(modified) = task.modified {
+        rows.push(("Modified", when(modified)));
+    }
+    if let Some(completed) = task.completed {
+        rows.push(("Completed", when(completed)));
+    }
+    if let Some(restored) = task.restored {
+        rows.push(("Restored", when(restored)));
+    }
+    let width = rows.iter().map(|(label, _)| label.len()).max().unwrap_or(0) + 1;
+    for (label, value) in rows {
+        println!("{:width$} {}", format!("{}:", label), value, width = width);
+    }
+
+    if !task.history.is_empty() {
+        println!();
+        println!("History");
+        for change in &task.history {
+            println!("  {}  {} → {}", when(change.at), change.from, change.to);
+        }
+    }
+    if !task.annotations.is_empty() {
+        println!();
+        println!("Annotations");
+        for annotation in &task.annotations {
+            println!("  {}  {}", when(annotation.at), annotation.text);
+        }
+    }
+    if let Some(notes) = &task.notes {
+        println!();
+        println!("Notes");
+        for line in notes.lines() {
+            if line.trim().is_empty() {
+                println!();
+            } else {
+                println!("  {}", line);
+            }
+        }
+    }
+}
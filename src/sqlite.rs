@@ -0,0 +1,227 @@
+use crate::error::Error;
+use crate::filter::Filter;
+use crate::schema::{self, Upgrade};
+use crate::storage::{self, Storage};
+use crate::task::Task;
+use crate::TaskList;
+use chrono::{DateTime, SecondsFormat, Utc};
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension};
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+
+/// Each task is stored whole as JSON in `data`, so the table never needs to
+/// change when `Task` gains a field. The other columns copy the fields
+/// filters use most, so that `ls` can narrow the search with the indexes.
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS meta (
+    key TEXT PRIMARY KEY,
+    value INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS tasks (
+    id INTEGER PRIMARY KEY,
+    status TEXT NOT NULL,
+    due TEXT,
+    priority INTEGER,
+    project TEXT,
+    data TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS tags (
+    task_id INTEGER NOT NULL,
+    tag TEXT NOT NULL,
+    PRIMARY KEY (task_id, tag)
+);
+CREATE INDEX IF NOT EXISTS tasks_status ON tasks (status);
+CREATE INDEX IF NOT EXISTS tasks_due ON tasks (due);
+CREATE INDEX IF NOT EXISTS tasks_priority ON tasks (priority);
+CREATE INDEX IF NOT EXISTS tasks_project ON tasks (project);
+CREATE INDEX IF NOT EXISTS tags_tag ON tags (tag);
+";
+
+/// Formats a time for the `due` column. Every value has the same width, so
+/// comparing the text compares the times.
+pub fn timestamp(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Nanos, true)
+}
+
+/// A task list kept in a SQLite database. The schema version of the stored
+/// tasks is kept in `PRAGMA user_version`. A new database starts at the
+/// current `schema::VERSION`; SQLite storage arrived with version 2, so no
+/// database holds anything older.
+pub struct Database {
+    path: PathBuf,
+    conn: Connection,
+}
+
+impl Database {
+    pub fn open(path: &Path) -> Result<Database, Error> {
+        let conn = Connection::open(path).map_err(|e| Error::database(path, e))?;
+        conn.execute_batch(SCHEMA)
+            .map_err(|e| Error::database(path, e))?;
+        let database = Database {
+            path: path.to_path_buf(),
+            conn,
+        };
+        if database.version()? == 0 {
+            database.set_version().map_err(|e| database.error(e))?;
+        }
+        Ok(database)
+    }
+
+    fn version(&self) -> Result<u64, Error> {
+        self.conn
+            .query_row("PRAGMA user_version", [], |row| row.get::<_, i64>(0))
+            .map(|version| version as u64)
+            .map_err(|e| self.error(e))
+    }
+
+    fn set_version(&self) -> rusqlite::Result<()> {
+        self.conn
+            .execute_batch(&format!("PRAGMA user_version = {}", schema::VERSION))
+    }
+
+    fn error(&self, e: rusqlite::Error) -> Error {
+        Error::database(&self.path, e)
+    }
+
+    /// Loads the tasks meeting `condition`, passing them through the same
+    /// schema upgrades as a JSON file.
+    fn select(&self, condition: &str, values: &[String]) -> Result<(TaskList, Upgrade), Error> {
+        let sql = format!("SELECT id, data FROM tasks WHERE {} ORDER BY id", condition);
+        let mut statement = self.conn.prepare(&sql).map_err(|e| self.error(e))?;
+        let rows = statement
+            .query_map(params_from_iter(values), |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+            })
+            .map_err(|e| self.error(e))?;
+        let mut tasks = Vec::new();
+        for row in rows {
+            let (id, data) = row.map_err(|e| self.error(e))?;
+            let mut task: Value =
+                serde_json::from_str(&data).map_err(|e| Error::json(&self.path, e))?;
+            task["id"] = json!(id);
+            tasks.push(task);
+        }
+
+        let stored: Option<i64> = self
+            .conn
+            .query_row("SELECT value FROM meta WHERE key = 'next_id'", [], |row| {
+                row.get(0)
+            })
+            .optional()
+            .map_err(|e| self.error(e))?;
+        let max_id: Option<i64> = self
+            .conn
+            .query_row("SELECT MAX(id) FROM tasks", [], |row| row.get(0))
+            .map_err(|e| self.error(e))?;
+        let next_id = stored.unwrap_or(1).max(max_id.unwrap_or(0) + 1);
+
+        let mut document = json!({
+            "version": self.version()?,
+            "next_id": next_id,
+            "tasks": tasks,
+        });
+        let upgrade = schema::upgrade(&self.path, &mut document)?;
+        let list = serde_json::from_value(document).map_err(|e| Error::json(&self.path, e))?;
+        Ok((storage::fix_next_id(list), upgrade))
+    }
+
+    fn write(&self, task: &Task, data: String) -> rusqlite::Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO tasks (id, status, due, priority, project, data)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                task.id as i64,
+                task.status.to_string(),
+                task.due.map(timestamp),
+                task.priority.map(|p| p as i64),
+                task.project,
+                data,
+            ],
+        )?;
+        self.conn
+            .execute("DELETE FROM tags WHERE task_id = ?1", [task.id as i64])?;
+        for tag in &task.tags {
+            self.conn.execute(
+                "INSERT INTO tags (task_id, tag) VALUES (?1, ?2)",
+                params![task.id as i64, tag],
+            )?;
+        }
+        Ok(())
+    }
+
+    fn delete(&self, id: usize) -> rusqlite::Result<()> {
+        self.conn
+            .execute("DELETE FROM tasks WHERE id = ?1", [id as i64])?;
+        self.conn
+            .execute("DELETE FROM tags WHERE task_id = ?1", [id as i64])?;
+        Ok(())
+    }
+}
+
+impl Storage for Database {
+    fn load_upgraded(&self) -> Result<(TaskList, Upgrade), Error> {
+        self.select("1", &[])
+    }
+
+    fn load_matching(&self, filters: &[Filter], now: DateTime<Utc>) -> Result<TaskList, Error> {
+        let mut conditions = Vec::new();
+        let mut values = Vec::new();
+        for filter in filters {
+            if let Some((condition, filter_values)) = filter.to_sql(now) {
+                conditions.push(format!("({})", condition));
+                values.extend(filter_values);
+            }
+        }
+        if conditions.is_empty() {
+            return self.load();
+        }
+        let condition = conditions.join(" AND ");
+        let with_dependencies = format!(
+            "({0}) OR id IN (SELECT j.value FROM tasks AS m, json_each(m.data, '$.depends_on') AS j \
+             WHERE m.id IN (SELECT id FROM tasks WHERE {0}))",
+            condition
+        );
+        values.extend(values.clone());
+        Ok(self.select(&with_dependencies, &values)?.0)
+    }
+
+    /// Writes only the tasks that differ from `before`, in one transaction.
+    /// A database from an older schema has every task rewritten, so that
+    /// all of them are stored in the current version.
+    fn save(&self, before: &TaskList, after: &TaskList) -> Result<(), Error> {
+        let upgrading = self.version()? < schema::VERSION;
+        let transaction = self
+            .conn
+            .unchecked_transaction()
+            .map_err(|e| self.error(e))?;
+        let result = (|| {
+            self.conn.execute(
+                "INSERT OR REPLACE INTO meta (key, value) VALUES ('next_id', ?1)",
+                [after.next_id as i64],
+            )?;
+            if upgrading {
+                self.set_version()?;
+            }
+            for (id, task) in &before.tasks {
+                if !after.tasks.contains_key(id) {
+                    self.delete(task.id)?;
+                }
+            }
+            for (id, task) in &after.tasks {
+                let data = serde_json::to_string(task).expect("tasks serialize to JSON");
+                let unchanged = !upgrading
+                    && before
+                        .tasks
+                        .get(id)
+                        .is_some_and(|old| serde_json::to_string(old).ok().as_ref() == Some(&data));
+                if !unchanged {
+                    self.write(task, data)?;
+                }
+            }
+            Ok(())
+        })();
+        result
+            .and_then(|_| transaction.commit())
+            .map_err(|e| self.error(e))
+    }
+}
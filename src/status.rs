@@ -0,0 +1,123 @@
+use crate::error::ParseError;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "kebab-case")]
+pub enum Status {
+    Pending,
+    InProgress,
+    Blocked,
+    Waiting,
+    Completed,
+    Cancelled,
+    /// The template of a recurring task, which is never worked on itself.
+    Recurring,
+}
+
+impl Status {
+    /// Whether the task still needs doing, i.e. it is neither completed nor
+    /// cancelled, nor a recurring template.
+    pub fn is_open(self) -> bool {
+        !matches!(
+            self,
+            Status::Completed | Status::Cancelled | Status::Recurring
+        )
+    }
+
+    pub fn glyph(self) -> char {
+        match self {
+            Status::Pending => ' ',
+            Status::InProgress => '•',
+            Status::Blocked => '!',
+            Status::Waiting => '…',
+            Status::Completed => '✓',
+            Status::Cancelled => '✗',
+            Status::Recurring => '↻',
+        }
+    }
+
+    /// Checks that a task may move from `self` to `to`. Open tasks may move
+    /// to any other state; closed tasks must be reopened to pending first.
+    /// Recurring templates are only changed through their series.
+    pub fn check_transition(self, to: Status) -> Result<(), TransitionError> {
+        if self == to {
+            Err(TransitionError::Unchanged(self))
+        } else if self == Status::Recurring || to == Status::Recurring {
+            Err(TransitionError::Template)
+        } else if !self.is_open() && to != Status::Pending {
+            Err(TransitionError::Closed { from: self, to })
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl FromStr for Status {
+    type Err = ParseError;
+    fn from_str(status: &str) -> Result<Self, Self::Err> {
+        match status.to_lowercase().as_str() {
+            "pending" => Ok(Status::Pending),
+            "in-progress" | "started" => Ok(Status::InProgress),
+            "blocked" => Ok(Status::Blocked),
+            "waiting" => Ok(Status::Waiting),
+            "completed" | "done" => Ok(Status::Completed),
+            "cancelled" | "canceled" => Ok(Status::Cancelled),
+            "recurring" => Ok(Status::Recurring),
+            _ => Err(ParseError::new("status", status)),
+        }
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Status::Pending => "pending",
+            Status::InProgress => "in-progress",
+            Status::Blocked => "blocked",
+            Status::Waiting => "waiting",
+            Status::Completed => "completed",
+            Status::Cancelled => "cancelled",
+            Status::Recurring => "recurring",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum TransitionError {
+    Unchanged(Status),
+    Closed { from: Status, to: Status },
+    NotClosed(Status),
+    Template,
+}
+
+impl fmt::Display for TransitionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TransitionError::Unchanged(status) => write!(f, "Task is already {}", status),
+            TransitionError::Closed { from, to } => {
+                write!(f, "Task is {}; reopen it before marking it {}", from, to)
+            }
+            TransitionError::NotClosed(status) => {
+                write!(f, "Task is {}, not completed or cancelled", status)
+            }
+            TransitionError::Template => write!(
+                f,
+                "Recurring templates change with their series; use modify --recur or --no-recur"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TransitionError {}
+
+/// One entry in a task's status history.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct StatusChange {
+    pub from: Status,
+    pub to: Status,
+    pub at: DateTime<Utc>,
+}
@@ -0,0 +1,119 @@
+use crate::error::Error;
+use crate::filter::Filter;
+use crate::schema::{self, Upgrade};
+use crate::sqlite::Database;
+use crate::{atomic, TaskList};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A place task lists are kept. The backend is chosen by file extension:
+/// `.db`, `.sqlite` and `.sqlite3` files are SQLite databases, anything
+/// else is a JSON file.
+pub trait Storage {
+    /// Loads the list, upgrading it from an older schema if need be, and
+    /// reports any upgrade applied. Nothing is written until it is saved.
+    fn load_upgraded(&self) -> Result<(TaskList, Upgrade), Error>;
+
+    fn load(&self) -> Result<TaskList, Error> {
+        Ok(self.load_upgraded()?.0)
+    }
+
+    /// Loads at least the tasks matching every filter, and the tasks those
+    /// depend on, which filters need to tell whether they are blocked.
+    /// Backends that can narrow the search themselves may skip the rest, but
+    /// callers must still apply the filters to what is returned.
+    fn load_matching(&self, _filters: &[Filter], _now: DateTime<Utc>) -> Result<TaskList, Error> {
+        self.load()
+    }
+
+    /// Stores `after`, where `before` is the list as it was loaded, so that
+    /// backends can write only what changed.
+    fn save(&self, before: &TaskList, after: &TaskList) -> Result<(), Error>;
+}
+
+const SQLITE_EXTENSIONS: [&str; 3] = ["db", "sqlite", "sqlite3"];
+
+fn is_sqlite(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| SQLITE_EXTENSIONS.iter().any(|e| ext == *e))
+}
+
+/// Whether `path` has an extension one of the backends stores tasks under.
+pub fn is_task_file(path: &Path) -> bool {
+    is_sqlite(path) || path.extension().is_some_and(|ext| ext == "json")
+}
+
+pub fn open(path: &Path) -> Result<Box<dyn Storage>, Error> {
+    if is_sqlite(path) {
+        Ok(Box::new(Database::open(path)?))
+    } else {
+        Ok(Box::new(JsonFile {
+            path: path.to_path_buf(),
+        }))
+    }
+}
+
+/// The whole list as one JSON document, rewritten on every save.
+pub struct JsonFile {
+    path: PathBuf,
+}
+
+/// The JSON file layout: the list with the schema version it was written in.
+#[derive(Serialize)]
+struct Envelope<'a> {
+    version: u64,
+    #[serde(flatten)]
+    tasks: &'a TaskList,
+}
+
+impl Storage for JsonFile {
+    /// Treats a missing or empty file as an empty list.
+    fn load_upgraded(&self) -> Result<(TaskList, Upgrade), Error> {
+        let path = &self.path;
+        let current = Upgrade {
+            from: schema::VERSION,
+            notes: Vec::new(),
+        };
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok((TaskList::default(), current))
+            }
+            Err(e) => return Err(Error::io("read", path, e)),
+        };
+        if contents.trim().is_empty() {
+            return Ok((TaskList::default(), current));
+        }
+        let mut document: Value =
+            serde_json::from_str(&contents).map_err(|e| Error::json(path, e))?;
+        let upgrade = schema::upgrade(path, &mut document)?;
+        // A file already at the current version is read again from the text,
+        // so that errors in it keep their line and column.
+        let list = if upgrade.from == schema::VERSION {
+            serde_json::from_str(&contents)
+        } else {
+            serde_json::from_value(document)
+        };
+        let list = list.map_err(|e| Error::json(path, e))?;
+        Ok((fix_next_id(list), upgrade))
+    }
+
+    fn save(&self, _before: &TaskList, after: &TaskList) -> Result<(), Error> {
+        let envelope = Envelope {
+            version: schema::VERSION,
+            tasks: after,
+        };
+        let json = serde_json::to_vec(&envelope).expect("tasks serialize to JSON");
+        atomic::write(&self.path, &json).map_err(|e| Error::io("write", &self.path, e))
+    }
+}
+
+/// Makes sure `next_id` is past every stored ID, whatever the file says.
+pub fn fix_next_id(mut list: TaskList) -> TaskList {
+    let max_id = list.tasks.keys().next_back().copied().unwrap_or(0);
+    list.next_id = list.next_id.max(max_id + 1);
+    list
+}
@@ -1,36 +1,366 @@
+use crate::error::ParseError;
+use crate::recur::Recurrence;
+use crate::status::{Status, StatusChange, TransitionError};
+use chrono::{DateTime, Duration, Utc};
 use serde::Deserialize;
 use serde::Serialize;
 use std::cmp::Ordering;
-use std::time::Instant;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::str::FromStr;
 
-pub const PENDING: char = ' ';
-pub const COMPLETED: char = '✓';
+/// Task priority; declaration order is sort order, most urgent first.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    High,
+    Medium,
+    Low,
+}
+
+impl FromStr for Priority {
+    type Err = ParseError;
+    fn from_str(priority: &str) -> Result<Self, Self::Err> {
+        match priority.to_lowercase().as_str() {
+            "h" | "high" => Ok(Priority::High),
+            "m" | "medium" => Ok(Priority::Medium),
+            "l" | "low" => Ok(Priority::Low),
+            _ => Err(ParseError::new("priority", priority)),
+        }
+    }
+}
+
+impl fmt::Display for Priority {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let letter = match self {
+            Priority::High => 'H',
+            Priority::Medium => 'M',
+            Priority::Low => 'L',
+        };
+        write!(f, "{}", letter)
+    }
+}
+
+/// Parses a tag as written on the command line, with or without its
+/// leading `+`.
+pub fn parse_tag(input: &str) -> Result<String, ParseError> {
+    let tag = input.strip_prefix('+').unwrap_or(input);
+    if tag.is_empty() || tag.contains(char::is_whitespace) {
+        return Err(ParseError::new("tag", input));
+    }
+    Ok(tag.to_string())
+}
+
+/// A timestamped remark appended to a task, such as a finding made while
+/// working on it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Annotation {
+    pub at: DateTime<Utc>,
+    pub text: String,
+}
+
+/// A stretch of time spent working on a task. The interval of the task
+/// being worked on now has no end yet.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Interval {
+    pub start: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end: Option<DateTime<Utc>>,
+}
+
+/// Field updates applied by `Task::modify`. Fields left as `None` are kept;
+/// for optional fields, `Some(None)` clears the value.
+#[derive(Debug, Default, Clone)]
+pub struct Changes {
+    pub description: Option<String>,
+    pub due: Option<Option<DateTime<Utc>>>,
+    pub priority: Option<Option<Priority>>,
+    pub project: Option<Option<String>>,
+    pub add_tags: Vec<String>,
+    pub remove_tags: Vec<String>,
+    pub parent: Option<Option<usize>>,
+    pub add_dependencies: Vec<usize>,
+    pub remove_dependencies: Vec<usize>,
+}
+
+impl Changes {
+    pub fn is_empty(&self) -> bool {
+        self.description.is_none()
+            && self.due.is_none()
+            && self.priority.is_none()
+            && self.project.is_none()
+            && self.add_tags.is_empty()
+            && self.remove_tags.is_empty()
+            && self.parent.is_none()
+            && self.add_dependencies.is_empty()
+            && self.remove_dependencies.is_empty()
+    }
+}
 
 #[derive(Serialize, Deserialize, Debug, Eq, Clone)]
 pub struct Task {
-    #[serde(with = "serde_millis")]
-    pub time: Instant,
+    #[serde(default)]
+    pub id: usize,
+    pub created: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completed: Option<DateTime<Utc>>,
+    /// When the task was last restored from the archive.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub restored: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub due: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<Priority>,
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub tags: BTreeSet<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project: Option<String>,
     pub description: String,
-    pub status: char,
+    pub status: Status,
+    /// Every status change, oldest first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub history: Vec<StatusChange>,
+    /// For a recurring template, when its instances fall due.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recur: Option<Recurrence>,
+    /// For an instance of a recurring task, the ID of its template.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<usize>,
+    /// The task this is a subtask of.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<usize>,
+    /// Tasks that must be closed before this one can start.
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub depends_on: BTreeSet<usize>,
+    /// Annotations, oldest first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub annotations: Vec<Annotation>,
+    /// Free-form notes, possibly spanning several lines.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+    /// Time spent working on the task, oldest first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub intervals: Vec<Interval>,
 }
 
 impl Task {
+    /// A pending task with no ID yet; one is assigned when it is added to a list.
     pub fn new(description: String) -> Task {
-        return Task {
-            time: Instant::now(),
-            description: description,
-            status: PENDING,
+        Task {
+            id: 0,
+            created: Utc::now(),
+            modified: None,
+            completed: None,
+            restored: None,
+            due: None,
+            priority: None,
+            tags: BTreeSet::new(),
+            project: None,
+            description,
+            status: Status::Pending,
+            history: Vec::new(),
+            recur: None,
+            template: None,
+            parent: None,
+            depends_on: BTreeSet::new(),
+            annotations: Vec::new(),
+            notes: None,
+            intervals: Vec::new(),
+        }
+    }
+
+    /// A recurring template copying this task's fields, to which this task
+    /// can then be linked as the first instance.
+    pub fn template(&self, recur: Recurrence) -> Task {
+        Task {
+            due: None,
+            priority: self.priority,
+            tags: self.tags.clone(),
+            project: self.project.clone(),
+            status: Status::Recurring,
+            recur: Some(recur),
+            parent: self.parent,
+            depends_on: self.depends_on.clone(),
+            ..Task::new(self.description.clone())
+        }
+    }
+
+    /// A new instance of this recurring template, due at `due`.
+    pub fn instance(&self, due: Option<DateTime<Utc>>) -> Task {
+        Task {
+            due,
+            priority: self.priority,
+            tags: self.tags.clone(),
+            project: self.project.clone(),
+            template: Some(self.id),
+            parent: self.parent,
+            depends_on: self.depends_on.clone(),
+            ..Task::new(self.description.clone())
+        }
+    }
+
+    /// Stops a recurring template from producing further instances. It
+    /// keeps its rule, so the series can be restarted.
+    pub fn stop_recurring(&mut self) {
+        self.move_template(Status::Cancelled);
+    }
+
+    /// Restarts a stopped template with `recur`.
+    pub fn restart_recurring(&mut self, recur: Recurrence) {
+        self.recur = Some(recur);
+        self.move_template(Status::Recurring);
+    }
+
+    fn move_template(&mut self, status: Status) {
+        let now = Utc::now();
+        self.history.push(StatusChange {
+            from: self.status,
+            to: status,
+            at: now,
+        });
+        self.status = status;
+        self.modified = Some(now);
+    }
+
+    /// Moves the task to `status`, recording the change in its history.
+    pub fn set_status(&mut self, status: Status) -> Result<(), TransitionError> {
+        if self.recur.is_some() {
+            return Err(TransitionError::Template);
+        }
+        self.status.check_transition(status)?;
+        let now = Utc::now();
+        self.history.push(StatusChange {
+            from: self.status,
+            to: status,
+            at: now,
+        });
+        if !status.is_open() {
+            self.stop_tracking(now);
+        }
+        self.completed = if status == Status::Completed {
+            Some(now)
+        } else {
+            None
         };
+        self.status = status;
+        self.modified = Some(now);
+        Ok(())
+    }
+
+    pub fn complete(&mut self) -> Result<(), TransitionError> {
+        self.set_status(Status::Completed)
+    }
+
+    /// Returns a completed or cancelled task to pending. The history keeps
+    /// when it was closed and when it was reopened.
+    pub fn reopen(&mut self) -> Result<(), TransitionError> {
+        if self.status.is_open() {
+            return Err(TransitionError::NotClosed(self.status));
+        }
+        self.set_status(Status::Pending)
+    }
+
+    /// Whether time is being tracked on the task right now.
+    pub fn is_active(&self) -> bool {
+        self.intervals.last().is_some_and(|i| i.end.is_none())
+    }
+
+    /// Starts tracking time on the task. A pending task moves to in-progress.
+    pub fn start(&mut self) -> Result<(), TransitionError> {
+        if self.status == Status::Pending {
+            self.set_status(Status::InProgress)?;
+        }
+        let now = Utc::now();
+        self.intervals.push(Interval {
+            start: now,
+            end: None,
+        });
+        self.modified = Some(now);
+        Ok(())
+    }
+
+    pub fn stop(&mut self) {
+        let now = Utc::now();
+        self.stop_tracking(now);
+        self.modified = Some(now);
+    }
+
+    fn stop_tracking(&mut self, now: DateTime<Utc>) {
+        if let Some(interval) = self.intervals.last_mut().filter(|i| i.end.is_none()) {
+            interval.end = Some(now);
+        }
+    }
+
+    /// The time tracked on the task between `from` and `to`, counting an
+    /// interval still running as ending at `now`.
+    pub fn tracked(&self, from: DateTime<Utc>, to: DateTime<Utc>, now: DateTime<Utc>) -> Duration {
+        self.intervals
+            .iter()
+            .map(|i| i.end.unwrap_or(now).min(to) - i.start.max(from))
+            .filter(|d| *d > Duration::zero())
+            .fold(Duration::zero(), |total, d| total + d)
+    }
+
+    pub fn annotate(&mut self, text: String) {
+        let now = Utc::now();
+        self.annotations.push(Annotation { at: now, text });
+        self.modified = Some(now);
+    }
+
+    pub fn set_notes(&mut self, notes: Option<String>) {
+        self.notes = notes;
+        self.modified = Some(Utc::now());
+    }
+
+    pub fn prioritize(&mut self, priority: Priority) {
+        self.priority = Some(priority);
+        self.modified = Some(Utc::now());
+    }
+
+    pub fn tag(&mut self, tags: &[String]) {
+        self.tags.extend(tags.iter().cloned());
+        self.modified = Some(Utc::now());
+    }
+
+    pub fn untag(&mut self, tags: &[String]) {
+        self.tags.retain(|tag| !tags.contains(tag));
+        self.modified = Some(Utc::now());
+    }
+
+    pub fn modify(&mut self, changes: Changes) {
+        if let Some(description) = changes.description {
+            self.description = description;
+        }
+        if let Some(due) = changes.due {
+            self.due = due;
+        }
+        if let Some(priority) = changes.priority {
+            self.priority = priority;
+        }
+        if let Some(project) = changes.project {
+            self.project = project;
+        }
+        self.tags.retain(|tag| !changes.remove_tags.contains(tag));
+        self.tags.extend(changes.add_tags);
+        if let Some(parent) = changes.parent {
+            self.parent = parent;
+        }
+        self.depends_on
+            .retain(|id| !changes.remove_dependencies.contains(id));
+        self.depends_on.extend(changes.add_dependencies);
+        self.modified = Some(Utc::now());
     }
 
-    pub fn complete(&mut self) {
-        self.status = COMPLETED;
+    pub fn is_overdue(&self, now: DateTime<Utc>) -> bool {
+        self.status.is_open() && self.due.is_some_and(|due| due < now)
     }
 }
 
 impl Ord for Task {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.time.cmp(&other.time)
+        self.created.cmp(&other.created)
     }
 }
 
@@ -42,6 +372,6 @@ impl PartialOrd for Task {
 
 impl PartialEq for Task {
     fn eq(&self, other: &Self) -> bool {
-        self.time == other.time
+        self.created == other.created
     }
 }
@@ -0,0 +1,152 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// A home directory of its own for each test, so that the config file and
+/// the task files in the data directory start out empty.
+struct Home {
+    dir: PathBuf,
+}
+
+impl Home {
+    fn new(name: &str) -> Home {
+        let dir = std::env::temp_dir().join(format!("todo-cli-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        Home { dir }
+    }
+
+    fn run(&self, args: &[&str]) -> Output {
+        Command::new(env!("CARGO_BIN_EXE_todo-cli"))
+            .args(args)
+            .env("HOME", &self.dir)
+            .env_remove("XDG_CONFIG_HOME")
+            .env_remove("XDG_DATA_HOME")
+            .env_remove("TODO_FILE")
+            .output()
+            .unwrap()
+    }
+
+    /// Runs `todo` with `args`, expecting it to succeed, and returns what it
+    /// printed.
+    fn todo(&self, args: &[&str]) -> String {
+        let output = self.run(args);
+        assert!(
+            output.status.success(),
+            "todo {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    fn path(&self, relative: &str) -> PathBuf {
+        self.dir.join(relative)
+    }
+}
+
+impl Drop for Home {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn read(path: &Path) -> String {
+    fs::read_to_string(path).unwrap_or_default()
+}
+
+#[test]
+fn restore_is_not_undone_by_archive_after() {
+    let home = Home::new("restore");
+    home.todo(&["config", "set", "archive-after", "0"]);
+    home.todo(&["add", "water", "the", "plants"]);
+    let done = home.todo(&["done", "1"]);
+    assert!(done.contains("Archived 1 task"), "{}", done);
+    let tasks = home.path(".local/share/todo/todo.json");
+    assert!(!read(&tasks).contains("water the plants"));
+
+    // Closed long ago, as far as archiving goes.
+    let archive = home.path(".local/share/todo/todo.archive.json");
+    let dates = regex::Regex::new(r#""\d{4}-(\d\d-\d\dT)"#).unwrap();
+    let backdated = dates.replace_all(&read(&archive), "\"2000-$1").into_owned();
+    fs::write(&archive, backdated).unwrap();
+
+    let restored = home.todo(&["restore", "1"]);
+    assert!(restored.contains("Restored task 1"), "{}", restored);
+    assert!(!restored.contains("Archived"), "{}", restored);
+    assert!(read(&tasks).contains("water the plants"));
+    assert!(home.todo(&["ls"]).contains("water the plants"));
+
+    // Counted from the restore rather than the close, which is years ago.
+    home.todo(&["config", "set", "archive-after", "1"]);
+    let added = home.todo(&["add", "repot", "the", "fern"]);
+    assert!(!added.contains("Archived"), "{}", added);
+    assert!(read(&tasks).contains("water the plants"));
+    let shown = home.todo(&["show", "1"]);
+    assert!(shown.contains("Restored:"), "{}", shown);
+}
+
+#[test]
+fn config_takes_archive_after_as_a_number() {
+    let home = Home::new("config");
+    let config = home.path(".config/todo/config.toml");
+    home.todo(&["config", "set", "archive-after", "30"]);
+    assert_eq!(read(&config), "archive-after = 30\n");
+    assert_eq!(home.todo(&["config", "get", "archive-after"]), "30\n");
+
+    fs::write(&config, "archive-after = \"14\"\n").unwrap();
+    home.todo(&["ls"]);
+    home.todo(&["config", "set", "sort", "due"]);
+    assert!(read(&config).contains("sort = \"due\""));
+
+    let refused = home.run(&["config", "set", "archive-after", "soon"]);
+    assert_eq!(refused.status.code(), Some(4));
+    assert!(!read(&config).contains("soon"));
+    home.todo(&["config", "unset", "archive-after"]);
+    assert!(!read(&config).contains("archive-after"));
+}
+
+#[test]
+fn add_takes_plus_words_as_tags() {
+    let home = Home::new("add-tags");
+    home.todo(&["add", "fix", "CI", "+work", "--tag", "urgent"]);
+    let listed = home.todo(&["ls", "-f", "+work and +urgent"]);
+    assert!(listed.contains("1 - fix CI "), "{}", listed);
+    assert!(!listed.contains("CI +work +"), "{}", listed);
+    assert_eq!(home.run(&["add", "+work"]).status.code(), Some(4));
+}
+
+#[test]
+fn undoing_a_move_restores_both_lists() {
+    let home = Home::new("move");
+    home.todo(&["add", "one"]);
+    home.todo(&["add", "two", "--depends-on", "1"]);
+    home.todo(&["--list", "work", "add", "existing"]);
+    home.todo(&["move", "1", "2", "--to", "work"]);
+    let work = home.todo(&["--list", "work", "ls"]);
+    assert!(work.contains("3 - two [!] (after 2)"), "{}", work);
+
+    let undone = home.todo(&["--list", "work", "undo"]);
+    assert!(undone.contains("undo: move 1 2 --to work"), "{}", undone);
+    let main = home.todo(&["ls"]);
+    assert!(
+        main.contains("1 - one") && main.contains("2 - two"),
+        "{}",
+        main
+    );
+    let work = home.todo(&["--list", "work", "ls"]);
+    assert!(
+        !work.contains("- one") && !work.contains("- two"),
+        "{}",
+        work
+    );
+
+    home.todo(&["redo"]);
+    assert!(!home.todo(&["ls"]).contains("- one"));
+    assert!(home.todo(&["--list", "work", "ls"]).contains("2 - one"));
+
+    home.todo(&["--list", "work", "add", "later"]);
+    let refused = home.run(&["undo"]);
+    assert_eq!(refused.status.code(), Some(4));
+    assert!(home.todo(&["--list", "work", "ls"]).contains("2 - one"));
+}